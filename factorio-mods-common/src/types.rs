@@ -57,6 +57,12 @@ impl AsRef<::semver::Version> for ReleaseVersion {
 	}
 }
 
+impl ::serde::Serialize for ReleaseVersion {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: ::serde::Serializer {
+		serializer.serialize_str(&self.0.to_string())
+	}
+}
+
 /// A username and token used with the parts of the web API that require authentication.
 #[derive(Clone, Debug, ::serde_derive::Deserialize)]
 pub struct UserCredentials {
@@ -7,6 +7,7 @@
 #[derive(Debug)]
 pub struct API {
 	game_version: ::factorio_mods_common::ReleaseVersion,
+	write_directory: ::std::path::PathBuf,
 	mods_directory: ::std::path::PathBuf,
 	mod_list_file_path: ::std::path::PathBuf,
 	player_data_json_file_path: ::std::path::PathBuf,
@@ -35,7 +36,7 @@ impl API {
 			base_info.version
 		};
 
-		let (mods_directory, mod_list_file_path, player_data_json_file_path) =
+		let (write_directory, mods_directory, mod_list_file_path, player_data_json_file_path) =
 			FACTORIO_SEARCH_PATHS.iter().filter_map(|search_path| {
 				let search_path = ::std::path::Path::new(search_path);
 
@@ -44,7 +45,7 @@ impl API {
 				let player_data_json_file_path = search_path.join("player-data.json");
 
 				if mod_list_file_path.is_file() && player_data_json_file_path.is_file() {
-					Some((mods_directory, mod_list_file_path, player_data_json_file_path))
+					Some((search_path.to_path_buf(), mods_directory, mod_list_file_path, player_data_json_file_path))
 				}
 				else {
 					None
@@ -53,6 +54,7 @@ impl API {
 
 		Ok(API {
 			game_version,
+			write_directory,
 			mods_directory,
 			mod_list_file_path,
 			player_data_json_file_path,
@@ -69,11 +71,58 @@ impl API {
 		&self.mods_directory
 	}
 
-	/// Returns an iterator over all the locally installed mods, matching the given name pattern if any.
+	/// Returns whether a Factorio process appears to be running against the write directory that contains the mods directory.
+	///
+	/// Factorio holds a lock on the `.lock` file in its write directory while it is running.
+	pub fn is_game_running(&self) -> bool {
+		let lock_file_path = self.write_directory.join(".lock");
+
+		let lock_file = match ::std::fs::File::open(&lock_file_path) {
+			Ok(lock_file) => lock_file,
+
+			// Windows doesn't allow opening the file at all while Factorio has it open. 32 is ERROR_SHARING_VIOLATION
+			Err(ref err) if cfg!(windows) && err.raw_os_error() == Some(32) => return true,
+
+			Err(_) => return false,
+		};
+
+		match ::fs2::FileExt::try_lock_shared(&lock_file) {
+			Ok(()) => {
+				let _ = ::fs2::FileExt::unlock(&lock_file);
+				false
+			},
+
+			Err(ref err) if err.raw_os_error() == ::fs2::lock_contended_error().raw_os_error() => true,
+
+			Err(_) => false,
+		}
+	}
+
+	/// Returns an iterator over all the locally installed mods.
 	pub fn installed_mods(&self) -> ::Result<impl Iterator<Item = ::Result<::InstalledMod>> + 'static> {
 		::installed_mod::find(&self.mods_directory, None, None)
 	}
 
+	/// Groups the locally installed mods by name, and determines which copy of each mod the game loads, taking into account
+	/// versions pinned in `mod-list.json`
+	pub fn effective_mods(&self) -> ::Result<::std::collections::BTreeMap<::factorio_mods_common::ModName, ::InstalledModVersions>> {
+		let mut installed_mods: ::std::collections::BTreeMap<_, Vec<_>> = Default::default();
+		for installed_mod in self.installed_mods()? {
+			let installed_mod = installed_mod?;
+			installed_mods.entry(installed_mod.info.name.clone()).or_insert_with(Vec::new).push(installed_mod);
+		}
+
+		let mod_list = self.mod_list()?;
+
+		Ok(
+			installed_mods.into_iter()
+			.filter_map(|(name, installed_mods)| {
+				let pinned_version = mod_list.get(&name).and_then(|entry| entry.version);
+				::effective::select_effective(installed_mods, pinned_version.as_ref()).map(|versions| (name, versions))
+			})
+			.collect())
+	}
+
 	/// Fetches the locally saved user credentials, if any.
 	pub fn user_credentials(&self) -> ::Result<::factorio_mods_common::UserCredentials> {
 		let player_data_json_file_path = &self.player_data_json_file_path;
@@ -109,52 +158,78 @@ impl API {
 
 		let player_data = player_data;
 
-		let mut player_data_json_file = match ::std::fs::File::create(player_data_json_file_path) {
-			Ok(player_data_json_file) => player_data_json_file,
-			Err(err) => bail!(::ErrorKind::FileIO(player_data_json_file_path.into(), err)),
-		};
-
-		::serde_json::to_writer_pretty(&mut player_data_json_file, &player_data).map_err(|err| ::ErrorKind::WriteJSONFile(player_data_json_file_path.into(), err).into())
+		// player-data.json also holds the player's settings, so keep a backup in case the new file turns out to be bad.
+		::atomic::write_json_atomic(player_data_json_file_path, ::atomic::Backup::Yes, &player_data)
 	}
 
 	/// Returns a map of installed mod name to its enabled / disabled status in `mod-list.json`
 	pub fn mods_status(&self) -> ::Result<::std::collections::HashMap<::factorio_mods_common::ModName, bool>> {
-		let mod_list = self.load_mod_list()?;
-		Ok(mod_list.mods.into_iter().map(|m| (m.name.into_owned(), m.enabled)).collect())
+		let mod_list = self.mod_list()?;
+		Ok(mod_list.entries().map(|entry| (entry.name, entry.enabled)).collect())
 	}
 
 	/// Marks the given locally installed mods as enabled or disabled in `mod-list.json`
 	pub fn set_enabled<'a, I>(&self, installed_mods: I, enabled: bool) -> ::Result<()> where I: IntoIterator<Item = &'a ::InstalledMod> {
-		let mod_list = self.load_mod_list()?;
-		let mut mods_status: ::std::collections::HashMap<_, _> = mod_list.mods.into_iter().map(|m| (m.name, m.enabled)).collect();
+		let mut mod_list = self.mod_list()?;
 
 		for installed_mod in installed_mods {
-			mods_status.insert(::std::borrow::Cow::Borrowed(&installed_mod.info.name), enabled);
+			mod_list.set_enabled(&installed_mod.info.name, enabled)?;
 		}
 
-		let mod_list_file_path = &self.mod_list_file_path;
-		let mut mod_list_file = match ::std::fs::File::create(mod_list_file_path) {
-			Ok(mod_list_file) => mod_list_file,
-			Err(err) => bail!(::ErrorKind::FileIO(mod_list_file_path.into(), err)),
-		};
+		self.save_mod_list(&mod_list)
+	}
+
+	/// Updates `mod-list.json` after mods have been installed and removed.
+	///
+	/// Entries of removed mods are deleted. Installed mods that don't have an entry get one that is enabled or disabled depending on `enable_new`.
+	/// Installed mods that already have an entry, such as upgraded ones, keep their enabled state, and their version is updated
+	/// if the entry pins one.
+	pub fn update_mod_list<'a, I, R>(&self, installed: I, removed: R, enable_new: bool) -> ::Result<()>
+		where
+			I: IntoIterator<Item = (&'a ::factorio_mods_common::ModName, &'a ::factorio_mods_common::ReleaseVersion)>,
+			R: IntoIterator<Item = &'a ::factorio_mods_common::ModName> {
+
+		let mut mod_list = self.mod_list()?;
+
+		for name in removed {
+			let _ = mod_list.remove(name)?;
+		}
 
-		let mut mods: Vec<_> =
-			mods_status.into_iter()
-			.map(|(name, enabled)| ModListMod { name, enabled })
-			.collect();
-		mods.sort_by(|mod1, mod2| mod1.name.cmp(&mod2.name));
+		for (name, version) in installed {
+			match mod_list.get(name) {
+				Some(::ModListEntry { version: Some(_), .. }) => mod_list.set_version(name, Some(version))?,
+				Some(_) => (),
+				None => mod_list.set_enabled(name, enable_new)?,
+			}
+		}
 
-		let mod_list = ModList { mods };
-		::serde_json::to_writer_pretty(&mut mod_list_file, &mod_list).map_err(|err| ::ErrorKind::WriteJSONFile(mod_list_file_path.into(), err).into())
+		self.save_mod_list(&mod_list)
 	}
 
-	fn load_mod_list(&self) -> ::Result<ModList<'static>> {
+	/// Loads `mod-list.json`
+	pub fn mod_list(&self) -> ::Result<::ModList> {
 		let mod_list_file_path = &self.mod_list_file_path;
-		let mod_list_file = match ::std::fs::File::open(mod_list_file_path) {
-			Ok(mod_list_file) => mod_list_file,
+		let contents = match ::std::fs::read_to_string(mod_list_file_path) {
+			Ok(contents) => contents,
 			Err(err) => bail!(::ErrorKind::FileIO(mod_list_file_path.into(), err)),
 		};
-		Ok(::serde_json::from_reader(mod_list_file).map_err(|err| ::ErrorKind::ReadJSONFile(mod_list_file_path.into(), err))?)
+		Ok(::ModList::parse(&contents).map_err(|err| ::ErrorKind::ReadJSONFile(mod_list_file_path.into(), err))?)
+	}
+
+	/// Saves the given mod list to `mod-list.json`
+	pub fn save_mod_list(&self, mod_list: &::ModList) -> ::Result<()> {
+		let mod_list_file_path = &self.mod_list_file_path;
+		let contents = mod_list.text();
+
+		// The mod list is changed by editing its text, so make sure the result is still valid JSON before overwriting the file.
+		if let Err(err) = ::serde_json::from_str::<::serde_json::Value>(contents) {
+			bail!(::ErrorKind::WriteJSONFile(mod_list_file_path.into(), err));
+		}
+
+		::atomic::write_atomic(mod_list_file_path, ::atomic::Backup::No, |writer| match ::std::io::Write::write_all(writer, contents.as_bytes()) {
+			Ok(()) => Ok(()),
+			Err(err) => bail!(::ErrorKind::FileIO(mod_list_file_path.into(), err)),
+		})
 	}
 }
 
@@ -219,21 +294,6 @@ lazy_static! {
 	};
 }
 
-/// Represents the contents of `mod-list.json`
-#[derive(Debug, ::serde_derive::Deserialize, ::serde_derive::Serialize)]
-struct ModList<'a> {
-	mods: Vec<ModListMod<'a>>,
-}
-
-/// A mod entry in the mod list
-#[derive(Debug, ::serde_derive::Deserialize, ::serde_derive::Serialize)]
-struct ModListMod<'a> {
-	name: ::std::borrow::Cow<'a, ::factorio_mods_common::ModName>,
-
-	#[serde(deserialize_with = "deserialize_mod_list_mod_enabled")]
-	enabled: bool,
-}
-
 /// Represents the contents of `base/info.json`
 #[derive(Debug, ::serde_derive::Deserialize)]
 struct BaseInfo {
@@ -249,28 +309,3 @@ struct PlayerData {
 	#[serde(rename(deserialize = "service-token"))]
 	service_token: Option<::factorio_mods_common::ServiceToken>,
 }
-
-/// Deserializes the `enabled` field of a mod in `mod-list.json`, which can be a JSON string or a JSON boolean.
-fn deserialize_mod_list_mod_enabled<'de, D>(deserializer: D) -> Result<bool, D::Error>
-	where D: ::serde::Deserializer<'de> {
-
-	struct Visitor;
-
-	impl<'de> ::serde::de::Visitor<'de> for Visitor {
-		type Value = bool;
-
-		fn expecting(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
-			write!(f, r#""true" or "false" or true or false"#)
-		}
-
-		fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E> where E: ::serde::de::Error {
-			Ok(v)
-		}
-
-		fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> where E: ::serde::de::Error {
-			v.parse().map_err(|_| ::serde::de::Error::invalid_value(::serde::de::Unexpected::Str(v), &self))
-		}
-	}
-
-	deserializer.deserialize_any(Visitor)
-}
@@ -0,0 +1,146 @@
+/// Whether `write_atomic` should keep a copy of the previous contents of the file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Backup {
+	/// Copy the previous contents of the file to a file with the same name and a `.bak` extension appended, such as `player-data.json.bak`
+	Yes,
+
+	/// Don't keep the previous contents.
+	No,
+}
+
+/// Replaces the contents of the given file with whatever the given function writes.
+///
+/// The new contents are written to a temporary file in the same directory, flushed to disk, and then renamed over the original file.
+/// So if the function fails or the process crashes, the original file is left untouched rather than truncated.
+pub fn write_atomic<F>(path: &::std::path::Path, backup: Backup, write: F) -> ::Result<()>
+	where F: FnOnce(&mut ::std::io::BufWriter<&mut ::std::fs::File>) -> ::Result<()> {
+
+	let temp_path = with_suffix(path, &format!(".{}.tmp", ::std::process::id()))?;
+
+	let result = replace(path, &temp_path, backup, write);
+
+	if result.is_err() {
+		let _ = ::std::fs::remove_file(&temp_path);
+	}
+
+	result
+}
+
+fn replace<F>(path: &::std::path::Path, temp_path: &::std::path::Path, backup: Backup, write: F) -> ::Result<()>
+	where F: FnOnce(&mut ::std::io::BufWriter<&mut ::std::fs::File>) -> ::Result<()> {
+
+	let mut file = match ::std::fs::File::create(temp_path) {
+		Ok(file) => file,
+		Err(err) => bail!(::ErrorKind::FileIO(temp_path.to_path_buf(), err)),
+	};
+
+	{
+		let mut writer = ::std::io::BufWriter::new(&mut file);
+		write(&mut writer)?;
+		if let Err(err) = ::std::io::Write::flush(&mut writer) {
+			bail!(::ErrorKind::FileIO(temp_path.to_path_buf(), err));
+		}
+	}
+
+	if let Err(err) = file.sync_all() {
+		bail!(::ErrorKind::FileIO(temp_path.to_path_buf(), err));
+	}
+
+	drop(file);
+
+	if backup == Backup::Yes {
+		let backup_path = with_suffix(path, ".bak")?;
+		match ::std::fs::copy(path, &backup_path) {
+			Ok(_) => (),
+			Err(ref err) if err.kind() == ::std::io::ErrorKind::NotFound => (),
+			Err(err) => bail!(::ErrorKind::FileIO(backup_path, err)),
+		}
+	}
+
+	if let Err(err) = ::std::fs::rename(temp_path, path) {
+		bail!(::ErrorKind::FileIO(path.to_path_buf(), err));
+	}
+
+	sync_parent_directory(path);
+
+	Ok(())
+}
+
+/// Serializes the given value as pretty-printed JSON and writes it to the given file with `write_atomic`
+pub fn write_json_atomic<T>(path: &::std::path::Path, backup: Backup, value: &T) -> ::Result<()> where T: ::serde::Serialize {
+	write_atomic(path, backup, |writer| {
+		::serde_json::to_writer_pretty(writer, value).map_err(|err| ::ErrorKind::WriteJSONFile(path.to_path_buf(), err).into())
+	})
+}
+
+/// Returns the given path with the given suffix appended to its file name.
+fn with_suffix(path: &::std::path::Path, suffix: &str) -> ::Result<::std::path::PathBuf> {
+	let mut file_name = match path.file_name() {
+		Some(file_name) => file_name.to_os_string(),
+		None => bail!(::ErrorKind::FileIO(path.to_path_buf(), ::std::io::Error::new(::std::io::ErrorKind::InvalidInput, "path has no file name"))),
+	};
+	file_name.push(suffix);
+	Ok(path.with_file_name(file_name))
+}
+
+/// Flushes the rename of a file in the given file's directory to disk. This is best-effort, since not all platforms and filesystems support it.
+#[cfg(unix)]
+fn sync_parent_directory(path: &::std::path::Path) {
+	if let Some(parent) = path.parent() {
+		let parent = if parent.as_os_str().is_empty() { ::std::path::Path::new(".") } else { parent };
+		if let Ok(directory) = ::std::fs::File::open(parent) {
+			let _ = directory.sync_all();
+		}
+	}
+}
+
+/// Flushes the rename of a file in the given file's directory to disk. Windows doesn't support opening directories as files, so this does nothing.
+#[cfg(windows)]
+fn sync_parent_directory(_: &::std::path::Path) {
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn temp_file(name: &str) -> ::std::path::PathBuf {
+		let directory = ::std::env::temp_dir().join(format!("factorio-mods-local-test-{}-{}", ::std::process::id(), name));
+		::std::fs::create_dir_all(&directory).unwrap();
+		directory.join("file.json")
+	}
+
+	#[test]
+	fn replaces_contents_and_keeps_backup() {
+		let path = temp_file("replaces_contents_and_keeps_backup");
+		::std::fs::write(&path, "old").unwrap();
+
+		write_atomic(&path, Backup::Yes, |writer| {
+			::std::io::Write::write_all(writer, b"new").unwrap();
+			Ok(())
+		}).unwrap();
+
+		assert_eq!(::std::fs::read_to_string(&path).unwrap(), "new");
+		assert_eq!(::std::fs::read_to_string(with_suffix(&path, ".bak").unwrap()).unwrap(), "old");
+
+		::std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+	}
+
+	#[test]
+	fn failed_write_leaves_original() {
+		let path = temp_file("failed_write_leaves_original");
+		::std::fs::write(&path, "old").unwrap();
+
+		let result = write_atomic(&path, Backup::No, |writer| {
+			::std::io::Write::write_all(writer, b"partial").unwrap();
+			bail!(::ErrorKind::FileIO(path.clone(), ::std::io::Error::new(::std::io::ErrorKind::Other, "serialization failed")))
+		});
+
+		assert!(result.is_err());
+		assert_eq!(::std::fs::read_to_string(&path).unwrap(), "old");
+
+		let remaining: Vec<_> = ::std::fs::read_dir(path.parent().unwrap()).unwrap().collect();
+		assert_eq!(remaining.len(), 1);
+
+		::std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+	}
+}
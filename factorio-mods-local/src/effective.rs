@@ -0,0 +1,107 @@
+/// All the installed copies of a mod, split into the one that the game loads and the ones that it ignores.
+#[derive(Clone, Debug)]
+pub struct InstalledModVersions {
+	/// The copy of the mod that the game loads.
+	pub effective: ::InstalledMod,
+
+	/// The other copies of the mod, which the game ignores.
+	pub shadowed: Vec<::InstalledMod>,
+}
+
+/// Chooses which of the given installed copies of a mod the game loads, the same way the game does.
+///
+/// If `mod-list.json` pins a version and that version is installed, that version is chosen. Otherwise the highest installed version is chosen.
+/// If the chosen version is installed more than once, such as both zipped and unpacked, the copy with the lowest path is chosen.
+///
+/// Returns `None` if `installed_mods` is empty.
+pub fn select_effective(
+	mut installed_mods: Vec<::InstalledMod>,
+	pinned_version: Option<&::factorio_mods_common::ReleaseVersion>,
+) -> Option<InstalledModVersions> {
+	installed_mods.sort_by(|mod1, mod2|
+		mod1.info.version.cmp(&mod2.info.version).reverse()
+		.then_with(|| mod1.path.cmp(&mod2.path)));
+
+	let index =
+		pinned_version
+		.and_then(|pinned_version| installed_mods.iter().position(|installed_mod| &installed_mod.info.version == pinned_version))
+		.unwrap_or(0);
+
+	if installed_mods.is_empty() {
+		return None;
+	}
+
+	let effective = installed_mods.remove(index);
+	Some(InstalledModVersions { effective, shadowed: installed_mods })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn installed_mod(version: &str, path: &str) -> ::InstalledMod {
+		::InstalledMod {
+			path: path.into(),
+			info: ::ModInfo {
+				name: ::factorio_mods_common::ModName("foo".to_string()),
+				author: vec![],
+				title: ::factorio_mods_common::ModTitle("Foo".to_string()),
+				description: None,
+				version: ::factorio_mods_common::ReleaseVersion(version.parse().unwrap()),
+				factorio_version: ::factorio_mods_common::ModVersionReq("0.16".parse().unwrap()),
+				homepage: None,
+				dependencies: vec![],
+			},
+			mod_type: if path.ends_with(".zip") { ::InstalledModType::Zipped } else { ::InstalledModType::Unpacked },
+		}
+	}
+
+	fn versions(installed_mod_versions: &InstalledModVersions) -> (&::std::path::Path, Vec<&::std::path::Path>) {
+		(
+			&installed_mod_versions.effective.path,
+			installed_mod_versions.shadowed.iter().map(|installed_mod| &*installed_mod.path).collect(),
+		)
+	}
+
+	#[test]
+	fn highest_version() {
+		let result = select_effective(vec![
+			installed_mod("1.0.0", "foo_1.0.0.zip"),
+			installed_mod("1.10.0", "foo_1.10.0.zip"),
+			installed_mod("1.2.0", "foo_1.2.0"),
+		], None).unwrap();
+
+		assert_eq!(versions(&result), (::std::path::Path::new("foo_1.10.0.zip"), vec![::std::path::Path::new("foo_1.2.0"), ::std::path::Path::new("foo_1.0.0.zip")]));
+	}
+
+	#[test]
+	fn pinned_version() {
+		let pinned = ::factorio_mods_common::ReleaseVersion("1.0.0".parse().unwrap());
+
+		let result = select_effective(vec![
+			installed_mod("1.0.0", "foo_1.0.0.zip"),
+			installed_mod("1.10.0", "foo_1.10.0.zip"),
+		], Some(&pinned)).unwrap();
+		assert_eq!(versions(&result), (::std::path::Path::new("foo_1.0.0.zip"), vec![::std::path::Path::new("foo_1.10.0.zip")]));
+
+		// A pinned version that isn't installed is ignored.
+		let result = select_effective(vec![
+			installed_mod("1.10.0", "foo_1.10.0.zip"),
+		], Some(&pinned)).unwrap();
+		assert_eq!(versions(&result), (::std::path::Path::new("foo_1.10.0.zip"), Vec::<&::std::path::Path>::new()));
+	}
+
+	#[test]
+	fn same_version() {
+		let result = select_effective(vec![
+			installed_mod("1.0.0", "foo_1.0.0.zip"),
+			installed_mod("1.0.0", "foo_1.0.0"),
+		], None).unwrap();
+		assert_eq!(versions(&result), (::std::path::Path::new("foo_1.0.0"), vec![::std::path::Path::new("foo_1.0.0.zip")]));
+	}
+
+	#[test]
+	fn empty() {
+		assert!(select_effective(vec![], None).is_none());
+	}
+}
@@ -45,6 +45,11 @@ pub enum ErrorKind {
 	#[error_chain(cause = |_, err| err)]
 	Pattern(String, ::globset::Error),
 
+	/// `mod-list.json` could not be changed because its text could not be scanned as a JSON object.
+	#[error_chain(custom)]
+	#[error_chain(display = const("mod-list.json is not a JSON object"))]
+	MalformedModList,
+
 	/// The local Factorio installation could not be found.
 	#[error_chain(custom)]
 	#[error_chain(display = const("The local Factorio installation could not be found"))]
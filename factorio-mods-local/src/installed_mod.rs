@@ -44,7 +44,7 @@ pub struct ModInfo {
 }
 
 /// The type of an installed mod.
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, ::serde_derive::Deserialize, ::serde_derive::Serialize)]
 pub enum InstalledModType {
 	/// A zipped mod.
 	Zipped,
@@ -18,6 +18,7 @@ extern crate derive_error_chain;
 extern crate error_chain;
 extern crate derive_struct;
 extern crate factorio_mods_common;
+extern crate fs2;
 extern crate globset;
 #[macro_use]
 extern crate lazy_static;
@@ -30,8 +31,17 @@ extern crate zip;
 mod api;
 pub use api::{ API };
 
+mod atomic;
+pub use atomic::{ Backup, write_atomic, write_json_atomic };
+
+mod effective;
+pub use effective::{ InstalledModVersions, select_effective };
+
 mod error;
 pub use error::{ Error, ErrorKind, Result, };
 
 mod installed_mod;
 pub use installed_mod::{ InstalledMod, InstalledModType, ModInfo };
+
+mod mod_list;
+pub use mod_list::{ ModList, ModListEntry };
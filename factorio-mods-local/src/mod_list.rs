@@ -0,0 +1,624 @@
+/// The contents of `mod-list.json`
+///
+/// Changes are made by editing the text of the file rather than serializing a new document, so saving it only changes the text of the entries
+/// that were modified. Fields this crate doesn't know about, the order of entries and keys, and the whitespace and line endings of the file
+/// are all preserved.
+#[derive(Clone, Debug)]
+pub struct ModList {
+	/// The text of the file, including all changes made so far.
+	text: String,
+
+	/// The formatting style of the file, used for text that has to be added.
+	style: Style,
+}
+
+/// An entry in `mod-list.json`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModListEntry {
+	/// The name of the mod.
+	pub name: ::factorio_mods_common::ModName,
+
+	/// Whether the mod is enabled.
+	pub enabled: bool,
+
+	/// The version of the mod that the game should load, if the entry pins one. Newer versions of the game support this
+	/// when more than one version of a mod is installed.
+	pub version: Option<::factorio_mods_common::ReleaseVersion>,
+}
+
+/// The formatting of a JSON file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Style {
+	indent: String,
+	crlf: bool,
+	trailing_newline: bool,
+}
+
+impl Default for Style {
+	fn default() -> Self {
+		Style { indent: "  ".to_string(), crlf: false, trailing_newline: true }
+	}
+}
+
+impl Default for ModList {
+	fn default() -> Self {
+		ModList { text: "{\n  \"mods\": []\n}\n".to_string(), style: Style::default() }
+	}
+}
+
+impl ModList {
+	/// Parses the given contents of `mod-list.json`
+	///
+	/// The contents must be a JSON object. Only the text is kept, since changes are made by editing it.
+	pub fn parse(s: &str) -> Result<Self, ::serde_json::Error> {
+		let _: ::serde_json::Map<String, ::serde_json::Value> = ::serde_json::from_str(s)?;
+		Ok(ModList { text: s.to_string(), style: Style::detect(s) })
+	}
+
+	/// Returns the text of this mod list, which is the text it was parsed from with any changes applied.
+	pub fn text(&self) -> &str {
+		&self.text
+	}
+
+	/// Returns the entries of this mod list in the order they appear in the file.
+	///
+	/// Entries that don't have a valid name and `enabled` field are skipped.
+	pub fn entries<'a>(&'a self) -> impl Iterator<Item = ModListEntry> + 'a {
+		scan_mods(&self.text).into_iter().flat_map(|mods| mods.elements).filter_map(move |element|
+			::serde_json::from_str::<::serde_json::Value>(&self.text[element.start..element.end]).ok()
+			.and_then(|value| parse_entry(&value)))
+	}
+
+	/// Returns the entry for the given mod, if any.
+	pub fn get(&self, name: &::factorio_mods_common::ModName) -> Option<ModListEntry> {
+		self.entries().find(|entry| &entry.name == name)
+	}
+
+	/// Marks the given mod as enabled or disabled. A new entry is added to the end of the list if the mod doesn't have one.
+	pub fn set_enabled(&mut self, name: &::factorio_mods_common::ModName, enabled: bool) -> ::Result<()> {
+		let mods = self.mods_span()?;
+
+		match self.find_entry(&mods, name) {
+			Some(entry) => match entry.member("enabled") {
+				Some(member) => {
+					// Older versions of the game wrote `"true"` and `"false"` strings, so keep using whichever form the entry already has.
+					let value =
+						if self.text[member.value_start..].starts_with('"') { format!(r#""{}""#, enabled) }
+						else { enabled.to_string() };
+					self.replace(member.value_start, member.value_end, &value);
+				},
+
+				None => self.insert_member(entry, "enabled", &enabled.to_string()),
+			},
+
+			None => self.append_entry(&mods, name, enabled, None),
+		}
+
+		Ok(())
+	}
+
+	/// Sets or clears the version that the game should load for the given mod. A new enabled entry is added to the end of the list
+	/// if the mod doesn't have one.
+	pub fn set_version(&mut self, name: &::factorio_mods_common::ModName, version: Option<&::factorio_mods_common::ReleaseVersion>) -> ::Result<()> {
+		let value = version.map(|version| ::serde_json::Value::String(version.0.to_string()).to_string());
+
+		let mods = self.mods_span()?;
+
+		match self.find_entry(&mods, name) {
+			Some(entry) => match (entry.members.iter().position(|member| member.key == "version"), value) {
+				(Some(index), Some(value)) => self.replace(entry.members[index].value_start, entry.members[index].value_end, &value),
+				(Some(index), None) => self.remove_item(&entry.members.iter().map(Member::span).collect::<Vec<_>>(), index),
+				(None, Some(value)) => self.insert_member(entry, "version", &value),
+				(None, None) => (),
+			},
+
+			None => self.append_entry(&mods, name, true, value.as_ref().map(String::as_str)),
+		}
+
+		Ok(())
+	}
+
+	/// Removes the entry for the given mod. Returns whether there was one.
+	pub fn remove(&mut self, name: &::factorio_mods_common::ModName) -> ::Result<bool> {
+		let mut removed = false;
+
+		loop {
+			let mods = self.mods_span()?;
+
+			let index = match mods.elements.iter().position(|element| self.is_entry_for(element, name)) {
+				Some(index) => index,
+				None => return Ok(removed),
+			};
+
+			self.remove_item(&mods.elements.iter().map(|element| (element.start, element.end)).collect::<Vec<_>>(), index);
+			removed = true;
+		}
+	}
+
+	/// Returns the span of the `mods` array, first replacing its value with an empty array if it's not an array, or adding an empty one
+	/// to the end of the file if it's missing.
+	fn mods_span(&mut self) -> ::Result<ArraySpan> {
+		if let Some(mods) = scan_mods(&self.text) {
+			return Ok(mods);
+		}
+
+		let document = scan_document(&self.text).ok_or(::ErrorKind::MalformedModList)?;
+		match document.member("mods") {
+			Some(member) => self.replace(member.value_start, member.value_end, "[]"),
+			None => self.insert_member(&document, "mods", "[]"),
+		}
+
+		Ok(scan_mods(&self.text).ok_or(::ErrorKind::MalformedModList)?)
+	}
+
+	/// Returns the span of the object in the given `mods` array that is the entry for the given mod.
+	fn find_entry<'a>(&self, mods: &'a ArraySpan, name: &::factorio_mods_common::ModName) -> Option<&'a ObjectSpan> {
+		mods.elements.iter().find(|element| self.is_entry_for(element, name)).and_then(|element| element.object.as_ref())
+	}
+
+	/// Returns whether the given element of the `mods` array is the entry for the given mod.
+	fn is_entry_for(&self, element: &ElementSpan, name: &::factorio_mods_common::ModName) -> bool {
+		element.object.as_ref().and_then(|object| object.member("name")).map_or(false, |member| {
+			let value = &self.text[member.value_start..member.value_end];
+			::serde_json::from_str::<String>(value).ok().as_ref() == Some(&name.0)
+		})
+	}
+
+	/// Adds a member with the given key and JSON value text to the end of the given object, formatted like its existing members.
+	fn insert_member(&mut self, object: &ObjectSpan, key: &str, value: &str) {
+		// The text between the key and the value, such as `": "`
+		let key_value_separator = object.members.first().map_or(": ".to_string(), |member| self.text[member.key_end..member.value_start].to_string());
+
+		let member = format!("{}{}{}", ::serde_json::Value::String(key.to_string()), key_value_separator, value);
+		let spans: Vec<_> = object.members.iter().map(Member::span).collect();
+		self.insert_item(object.start + 1, &spans, &member);
+	}
+
+	/// Adds a new entry to the end of the given `mods` array, formatted like the existing entries.
+	fn append_entry(&mut self, mods: &ArraySpan, name: &::factorio_mods_common::ModName, enabled: bool, version: Option<&str>) {
+		let mut members = vec![
+			("name", ::serde_json::Value::String(name.0.clone()).to_string()),
+			("enabled", enabled.to_string()),
+		];
+		if let Some(version) = version {
+			members.push(("version", version.to_string()));
+		}
+
+		let newline = if self.style.crlf { "\r\n" } else { "\n" };
+
+		let last = mods.elements.last();
+
+		let entry = match last.and_then(|last| last.object.as_ref().map(|object| (last, object))) {
+			// Copy the layout of an existing entry that's on a single line, such as `{ "name": "base", "enabled": true }`
+			Some((last, object)) if !self.text[last.start..last.end].contains('\n') && !object.members.is_empty() => {
+				let first_member = &object.members[0];
+				let last_member = &object.members[object.members.len() - 1];
+
+				let leading = &self.text[object.start + 1..first_member.key_start];
+				let trailing = &self.text[last_member.value_end..object.end - 1];
+				let key_value_separator = &self.text[first_member.key_end..first_member.value_start];
+				let member_separator = if object.members.len() >= 2 { &self.text[first_member.value_end..object.members[1].key_start] } else { ", " };
+
+				let members: Vec<_> =
+					members.iter()
+					.map(|&(key, ref value)| format!("{}{}{}", ::serde_json::Value::String(key.to_string()), key_value_separator, value))
+					.collect();
+
+				format!("{{{}{}{}}}", leading, members.join(member_separator), trailing)
+			},
+
+			_ => {
+				let indent = match last {
+					Some(last) => line_indent(&self.text, last.start).to_string(),
+					None => format!("{}{}", line_indent(&self.text, mods.start), self.style.indent),
+				};
+
+				let mut lines = vec!["{".to_string()];
+				for (i, &(key, ref value)) in members.iter().enumerate() {
+					lines.push(format!(
+						"{}{}{}: {}{}",
+						indent, self.style.indent, ::serde_json::Value::String(key.to_string()), value,
+						if i + 1 < members.len() { "," } else { "" }));
+				}
+				lines.push(format!("{}}}", indent));
+				lines.join(newline)
+			},
+		};
+
+		if mods.elements.is_empty() {
+			// The array is `[]` or only has whitespace inside, so lay it out like the rest of the file.
+			let closing_indent = line_indent(&self.text, mods.start).to_string();
+			let replacement = format!("{}{}{}{}{}{}", newline, closing_indent, self.style.indent, entry, newline, closing_indent);
+			self.replace(mods.start + 1, mods.end - 1, &replacement);
+		}
+		else {
+			let spans: Vec<_> = mods.elements.iter().map(|element| (element.start, element.end)).collect();
+			self.insert_item(mods.start + 1, &spans, &entry);
+		}
+	}
+
+	/// Inserts the given text as a new last item of an array or object whose opening bracket ends at `open`, and whose existing items
+	/// have the given spans. The new item is separated from the previous one the same way as the existing items are separated from each other.
+	fn insert_item(&mut self, open: usize, spans: &[(usize, usize)], item: &str) {
+		match spans.len() {
+			0 => self.replace(open, open, item),
+
+			len => {
+				let separator =
+					if len >= 2 { self.text[spans[len - 2].1..spans[len - 1].0].to_string() }
+					else { format!(",{}", &self.text[open..spans[0].0]) };
+
+				let end = spans[len - 1].1;
+				self.replace(end, end, &format!("{}{}", separator, item));
+			},
+		}
+	}
+
+	/// Removes the item at the given index of an array or object whose items have the given spans, along with the separator before or after it.
+	fn remove_item(&mut self, spans: &[(usize, usize)], index: usize) {
+		let (start, end) =
+			if index + 1 < spans.len() { (spans[index].0, spans[index + 1].0) }
+			else if index > 0 { (spans[index - 1].1, spans[index].1) }
+			else { spans[index] };
+
+		self.replace(start, end, "");
+	}
+
+	/// Replaces the given range of the text.
+	fn replace(&mut self, start: usize, end: usize, replacement: &str) {
+		self.text = format!("{}{}{}", &self.text[..start], replacement, &self.text[end..]);
+	}
+}
+
+fn parse_entry(value: &::serde_json::Value) -> Option<ModListEntry> {
+	let name = value.get("name")?.as_str()?;
+
+	let enabled = match *value.get("enabled")? {
+		::serde_json::Value::Bool(enabled) => enabled,
+		::serde_json::Value::String(ref enabled) => enabled.parse().ok()?,
+		_ => return None,
+	};
+
+	let version = value.get("version").and_then(|version| ::serde_json::from_value(version.clone()).ok());
+
+	Some(ModListEntry { name: ::factorio_mods_common::ModName(name.to_string()), enabled, version })
+}
+
+/// Returns the whitespace at the start of the line that contains the given position, up to that position.
+fn line_indent(text: &str, position: usize) -> &str {
+	let line_start = text[..position].rfind('\n').map_or(0, |index| index + 1);
+	let prefix = &text[line_start..position];
+	let indent_len = prefix.len() - prefix.trim_left_matches(|c| c == ' ' || c == '\t').len();
+	&prefix[..indent_len]
+}
+
+/// The location of a JSON array in the text of a file.
+#[derive(Debug)]
+struct ArraySpan {
+	/// The position of the `[`
+	start: usize,
+
+	/// The position after the `]`
+	end: usize,
+
+	elements: Vec<ElementSpan>,
+}
+
+/// The location of a value in a JSON array.
+#[derive(Debug)]
+struct ElementSpan {
+	start: usize,
+	end: usize,
+
+	/// The members of the value, if it's an object.
+	object: Option<ObjectSpan>,
+}
+
+/// The location of a JSON object in the text of a file.
+#[derive(Debug)]
+struct ObjectSpan {
+	/// The position of the `{`
+	start: usize,
+
+	/// The position after the `}`
+	end: usize,
+
+	members: Vec<Member>,
+}
+
+/// The location of a member of a JSON object.
+#[derive(Debug)]
+struct Member {
+	key: String,
+
+	/// The position of the opening quote of the key.
+	key_start: usize,
+
+	/// The position after the closing quote of the key.
+	key_end: usize,
+
+	value_start: usize,
+	value_end: usize,
+}
+
+impl ObjectSpan {
+	fn member(&self, key: &str) -> Option<&Member> {
+		self.members.iter().find(|member| member.key == key)
+	}
+}
+
+impl Member {
+	fn span(&self) -> (usize, usize) {
+		(self.key_start, self.value_end)
+	}
+}
+
+/// Finds the top-level object of the given mod list text. Returns `None` if the text isn't an object.
+fn scan_document(text: &str) -> Option<ObjectSpan> {
+	let mut scanner = Scanner { text, position: 0 };
+	scanner.skip_whitespace();
+	scanner.object()
+}
+
+/// Finds the `mods` array of the given mod list text. Returns `None` if the text isn't an object with a `mods` array.
+fn scan_mods(text: &str) -> Option<ArraySpan> {
+	let document = scan_document(text)?;
+
+	let mods = document.member("mods")?;
+	if !text[mods.value_start..].starts_with('[') {
+		return None;
+	}
+
+	let mut scanner = Scanner { text, position: mods.value_start };
+	scanner.array()
+}
+
+/// Finds the locations of JSON values in text that is already known to be valid JSON.
+struct Scanner<'a> {
+	text: &'a str,
+	position: usize,
+}
+
+impl<'a> Scanner<'a> {
+	fn peek(&self) -> Option<u8> {
+		self.text.as_bytes().get(self.position).cloned()
+	}
+
+	fn skip_whitespace(&mut self) {
+		while self.peek().map_or(false, |b| b == b' ' || b == b'\t' || b == b'\r' || b == b'\n') {
+			self.position += 1;
+		}
+	}
+
+	fn expect(&mut self, expected: u8) -> Option<()> {
+		self.skip_whitespace();
+		if self.peek() != Some(expected) {
+			return None;
+		}
+
+		self.position += 1;
+		Some(())
+	}
+
+	/// Scans any value, and returns its start and end positions.
+	fn value(&mut self) -> Option<(usize, usize)> {
+		self.skip_whitespace();
+		let start = self.position;
+
+		match self.peek()? {
+			b'"' => self.string(),
+			b'{' => self.object().map(|object| (object.start, object.end)),
+			b'[' => self.array().map(|array| (array.start, array.end)),
+			_ => {
+				while let Some(b) = self.peek() {
+					match b {
+						b',' | b']' | b'}' | b' ' | b'\t' | b'\r' | b'\n' => break,
+						_ => self.position += 1,
+					}
+				}
+
+				if self.position == start { None } else { Some((start, self.position)) }
+			},
+		}
+	}
+
+	fn string(&mut self) -> Option<(usize, usize)> {
+		let start = self.position;
+		self.position += 1;
+
+		loop {
+			match self.peek()? {
+				b'\\' => self.position += 2,
+				b'"' => {
+					self.position += 1;
+					return Some((start, self.position));
+				},
+				_ => self.position += 1,
+			}
+		}
+	}
+
+	fn object(&mut self) -> Option<ObjectSpan> {
+		self.expect(b'{')?;
+		let start = self.position - 1;
+
+		let mut members = vec![];
+
+		self.skip_whitespace();
+		if self.peek() == Some(b'}') {
+			self.position += 1;
+			return Some(ObjectSpan { start, end: self.position, members });
+		}
+
+		loop {
+			self.skip_whitespace();
+			if self.peek() != Some(b'"') {
+				return None;
+			}
+
+			let (key_start, key_end) = self.string()?;
+			let key = ::serde_json::from_str(&self.text[key_start..key_end]).ok()?;
+			self.expect(b':')?;
+			let (value_start, value_end) = self.value()?;
+			members.push(Member { key, key_start, key_end, value_start, value_end });
+
+			self.skip_whitespace();
+			match self.peek()? {
+				b',' => self.position += 1,
+				b'}' => {
+					self.position += 1;
+					return Some(ObjectSpan { start, end: self.position, members });
+				},
+				_ => return None,
+			}
+		}
+	}
+
+	fn array(&mut self) -> Option<ArraySpan> {
+		self.expect(b'[')?;
+		let start = self.position - 1;
+
+		let mut elements = vec![];
+
+		self.skip_whitespace();
+		if self.peek() == Some(b']') {
+			self.position += 1;
+			return Some(ArraySpan { start, end: self.position, elements });
+		}
+
+		loop {
+			self.skip_whitespace();
+
+			let element =
+				if self.peek() == Some(b'{') {
+					let object = self.object()?;
+					ElementSpan { start: object.start, end: object.end, object: Some(object) }
+				}
+				else {
+					let (start, end) = self.value()?;
+					ElementSpan { start, end, object: None }
+				};
+			elements.push(element);
+
+			self.skip_whitespace();
+			match self.peek()? {
+				b',' => self.position += 1,
+				b']' => {
+					self.position += 1;
+					return Some(ArraySpan { start, end: self.position, elements });
+				},
+				_ => return None,
+			}
+		}
+	}
+}
+
+impl Style {
+	/// Detects the formatting style of the given JSON text.
+	fn detect(s: &str) -> Self {
+		let default = Style::default();
+
+		// The first indented line is nested one level deep, so its leading whitespace is one level of indentation.
+		let indent =
+			s.lines()
+			.map(|line| &line[..line.len() - line.trim_left_matches(|c| c == ' ' || c == '\t').len()])
+			.find(|indent| !indent.is_empty())
+			.map_or(default.indent, str::to_string);
+
+		Style {
+			indent,
+			crlf: s.contains("\r\n"),
+			trailing_newline: s.ends_with('\n'),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn name(s: &str) -> ::factorio_mods_common::ModName {
+		::factorio_mods_common::ModName(s.to_string())
+	}
+
+	#[test]
+	fn round_trip() {
+		let s = "{\r\n\t\"mods\": [\r\n\t\t{\r\n\t\t\t\"name\": \"zzz\",\r\n\t\t\t\"enabled\": \"true\",\r\n\t\t\t\"future\": [1, 2]\r\n\t\t},\r\n\t\t{ \"name\": \"base\", \"enabled\": true }\r\n\t],\r\n\t\"other\": 5\r\n}";
+
+		let mod_list = ModList::parse(s).unwrap();
+		assert_eq!(mod_list.text(), s);
+	}
+
+	#[test]
+	fn modify() {
+		let s = r#"{
+    "mods": [
+        { "name": "zzz", "enabled": "true", "future": [1, 2] },
+        { "name": "aaa", "enabled": false, "version": "1.2.3" },
+        { "name": "base", "enabled": true }
+    ]
+}
+"#;
+
+		let mut mod_list = ModList::parse(s).unwrap();
+
+		assert_eq!(mod_list.get(&name("aaa")), Some(ModListEntry {
+			name: name("aaa"),
+			enabled: false,
+			version: Some(::factorio_mods_common::ReleaseVersion("1.2.3".parse().unwrap())),
+		}));
+
+		mod_list.set_enabled(&name("zzz"), false).unwrap();
+		mod_list.set_version(&name("aaa"), None).unwrap();
+		assert!(mod_list.remove(&name("base")).unwrap());
+		assert!(!mod_list.remove(&name("base")).unwrap());
+		mod_list.set_enabled(&name("new"), false).unwrap();
+
+		let expected = r#"{
+    "mods": [
+        { "name": "zzz", "enabled": "false", "future": [1, 2] },
+        { "name": "aaa", "enabled": false },
+        { "name": "new", "enabled": false }
+    ]
+}
+"#;
+		assert_eq!(mod_list.text(), expected);
+
+		let names: Vec<_> = mod_list.entries().map(|entry| entry.name.0).collect();
+		assert_eq!(names, ["zzz", "aaa", "new"]);
+	}
+
+	#[test]
+	fn modify_multiline() {
+		let s = "{\r\n\t\"mods\": [\r\n\t\t{\r\n\t\t\t\"name\": \"base\",\r\n\t\t\t\"enabled\": true\r\n\t\t},\r\n\t\t{\r\n\t\t\t\"name\": \"zzz\",\r\n\t\t\t\"enabled\": false\r\n\t\t}\r\n\t]\r\n}\r\n";
+
+		let mut mod_list = ModList::parse(s).unwrap();
+
+		mod_list.set_version(&name("zzz"), Some(&::factorio_mods_common::ReleaseVersion("1.0.0".parse().unwrap()))).unwrap();
+		mod_list.set_enabled(&name("new"), true).unwrap();
+
+		let expected = "{\r\n\t\"mods\": [\r\n\t\t{\r\n\t\t\t\"name\": \"base\",\r\n\t\t\t\"enabled\": true\r\n\t\t},\r\n\t\t{\r\n\t\t\t\"name\": \"zzz\",\r\n\t\t\t\"enabled\": false,\r\n\t\t\t\"version\": \"1.0.0\"\r\n\t\t},\r\n\t\t{\r\n\t\t\t\"name\": \"new\",\r\n\t\t\t\"enabled\": true\r\n\t\t}\r\n\t]\r\n}\r\n";
+		assert_eq!(mod_list.text(), expected);
+	}
+
+	#[test]
+	fn empty() {
+		let mut mod_list = ModList::parse("{\n  \"mods\": []\n}\n").unwrap();
+		mod_list.set_enabled(&name("new"), true).unwrap();
+		assert_eq!(mod_list.text(), "{\n  \"mods\": [\n    {\n      \"name\": \"new\",\n      \"enabled\": true\n    }\n  ]\n}\n");
+	}
+	#[test]
+	fn missing_mods() {
+		let mut mod_list = ModList::parse(r#"{"other": 1}"#).unwrap();
+		mod_list.set_enabled(&name("new"), true).unwrap();
+		assert!(mod_list.text().starts_with(r#"{"other": 1,"mods": ["#));
+
+		let names: Vec<_> = mod_list.entries().map(|entry| entry.name.0).collect();
+		assert_eq!(names, ["new"]);
+
+		let mut mod_list = ModList::parse(r#"{"mods": 5, "other": 1}"#).unwrap();
+		mod_list.set_enabled(&name("new"), true).unwrap();
+		assert!(mod_list.text().starts_with(r#"{"mods": ["#));
+		assert!(mod_list.text().ends_with(r#"], "other": 1}"#));
+	}
+}
@@ -2,7 +2,7 @@
 	single_match_else,
 ))]
 
-use ::futures::{ Future, Poll, stream, Stream };
+use ::futures::{ future, Future, Poll, Stream };
 
 /// Entry-point to the <https://mods.factorio.com/> API
 #[derive(Debug)]
@@ -17,41 +17,43 @@ impl API {
 	/// Constructs an API object with the given parameters.
 	pub fn new(
 		builder: Option<::reqwest::unstable::async::ClientBuilder>,
+		retry_policy: ::RetryPolicy,
 		handle: ::tokio_core::reactor::Handle,
 	) -> ::Result<Self> {
 		Ok(API {
 			base_url: BASE_URL.clone(),
 			mods_url: MODS_URL.clone(),
 			login_url: LOGIN_URL.clone(),
-			client: ::client::Client::new(builder, handle)?,
+			client: ::client::Client::new(builder, retry_policy, handle)?,
 		})
 	}
 
-	/// Searches for mods matching the given criteria.
+	/// Searches for mods whose name, title, owners or summary contain the given text.
 	pub fn search<'a>(
 		&'a self,
 		query: &'a str,
 	) -> impl Stream<Item = ::SearchResponseMod, Error = ::Error> + 'a {
-		::async_stream_block! {
-			let query = query.to_lowercase();
+		self.search_query(::SearchQuery::new(query))
+	}
 
-			let mut next_page_url = Some(self.mods_url.clone());
+	/// Searches for mods matching the given query.
+	pub fn search_query<'a>(
+		&'a self,
+		query: ::SearchQuery,
+	) -> impl Stream<Item = ::SearchResponseMod, Error = ::Error> + 'a {
+		::async_stream_block! {
+			let mut next_page_url = Some(query.url(&self.mods_url));
 
 			while let Some(url) = next_page_url {
 				match ::await!(self.client.get_object::<PagedResponse<::SearchResponseMod>>(url)) {
 					Ok((page, _)) => {
 						for mod_ in page.results {
-							if
-								mod_.name.0.to_lowercase().contains(&query) ||
-								mod_.title.0.to_lowercase().contains(&query) ||
-								mod_.owner.iter().any(|owner| owner.0.to_lowercase().contains(&query)) ||
-								mod_.summary.0.to_lowercase().contains(&query)
-							{
+							if query.matches(&mod_) {
 								::stream_yield!(mod_);
 							}
 						}
 
-						next_page_url = page.pagination.links.next;
+						next_page_url = if query.is_single_page() { None } else { page.pagination.links.next };
 					},
 
 					Err(::Error(::ErrorKind::StatusCode(_, ::reqwest::StatusCode::NotFound), _)) =>
@@ -77,6 +79,35 @@ impl API {
 		}
 	}
 
+	/// Gets all the details of the specified mod, including the dependencies of each release.
+	pub fn get_full(&self, mod_name: &::factorio_mods_common::ModName) -> impl Future<Item = ::ModFull, Error = ::Error> + 'static {
+		let mut mod_url = self.mods_url.clone();
+		mod_url.path_segments_mut().unwrap().push(&mod_name.0).push("full");
+		let future = self.client.get_object(mod_url);
+
+		::async_block! {
+			let (mod_, _) = ::await!(future)?;
+			Ok(mod_)
+		}
+	}
+
+	/// Gets information about the specified mods.
+	///
+	/// The mods are fetched in batches using the name list of the mods portal, so this makes far fewer requests than calling `API::get`
+	/// for each mod. Mods that don't exist are not included in the result.
+	pub fn get_many(&self, mod_names: &[::factorio_mods_common::ModName]) -> impl Future<Item = Vec<::Mod>, Error = ::Error> + 'static {
+		let futures: Vec<_> =
+			mod_names.chunks(GET_MANY_BATCH_SIZE)
+			.map(|mod_names| {
+				let url = ::SearchQuery::default().namelist(mod_names.iter().cloned()).url(&self.mods_url);
+				self.client.get_object::<PagedResponse<::Mod>>(url)
+			})
+			.collect();
+
+		future::join_all(futures)
+		.map(|pages| pages.into_iter().flat_map(|(page, _)| page.results).collect())
+	}
+
 	/// Logs in to the web API using the given username and password and returns a credentials object.
 	pub fn login(
 		&self,
@@ -91,12 +122,16 @@ impl API {
 		}
 	}
 
-	/// Downloads the file for the specified mod release and returns a reader to the file contents.
+	/// Downloads the file for the specified mod release. Resolves to a stream of the file contents once the server has responded.
+	///
+	/// If `resume_from` is set, the server is asked to only send the contents from that offset onwards, to resume a previous download.
+	/// Check `Download::resumed_from` to find out whether it did.
 	pub fn download(
 		&self,
 		release: &::ModRelease,
 		user_credentials: &::factorio_mods_common::UserCredentials,
-	) -> impl Stream<Item = ::reqwest::unstable::async::Chunk, Error = ::Error> + 'static {
+		resume_from: Option<u64>,
+	) -> impl Future<Item = Download, Error = ::Error> + 'static {
 		let download_url = match self.base_url.join(&release.download_url.0) {
 			Ok(mut download_url) => {
 				download_url.query_pairs_mut()
@@ -107,26 +142,65 @@ impl API {
 			},
 
 			Err(err) =>
-				return Either::A(stream::once(Err(::ErrorKind::Parse(format!("{}/{}", self.base_url, release.download_url), err).into()))),
+				return Either::A(future::err(::ErrorKind::Parse(format!("{}/{}", self.base_url, release.download_url), err).into())),
 		};
 
-		let future = self.client.get_zip(download_url);
+		let future = self.client.get_zip(download_url, resume_from);
 
-		#[cfg_attr(feature = "cargo-clippy", allow(unit_arg))]
-		Either::B(::async_stream_block! {
-			let (response, download_url) = ::await!(future)?;
+		Either::B(::async_block! {
+			let (response, url) = ::await!(future)?;
 
-			let result: Result<_, ::reqwest::Error> = do catch {
-				#[async] for chunk in response.into_body() {
-					::stream_yield!(chunk);
-				}
-			};
+			let content_length = response.headers().get::<::reqwest::header::ContentLength>().map(|content_length| content_length.0);
+
+			let resumed_from =
+				if response.status() == ::reqwest::StatusCode::PartialContent {
+					match response.headers().get() {
+						Some(&::reqwest::header::ContentRange(::reqwest::header::ContentRangeSpec::Bytes { range: Some((start, _)), .. }))
+							if Some(start) == resume_from => resume_from,
 
-			result.map_err(|err| ::ErrorKind::HTTP(download_url, err).into())
+						_ => bail!(::ErrorKind::MalformedResponse(url, "Unexpected Content-Range header".to_string())),
+					}
+				}
+				else {
+					None
+				};
+
+			Ok(Download {
+				content_length,
+				resumed_from,
+				body: response.into_body(),
+				url,
+			})
 		})
 	}
 }
 
+/// A mod release file being downloaded, returned by `API::download`
+///
+/// This is a stream of the contents of the file.
+#[derive(Debug)]
+pub struct Download {
+	/// The number of bytes in this stream, if the server reported it. If the download was resumed, this doesn't include the bytes before
+	/// `resumed_from`
+	pub content_length: Option<u64>,
+
+	/// The offset in the file that this stream starts at, if the server resumed the download. `None` if this stream starts at the beginning
+	/// of the file.
+	pub resumed_from: Option<u64>,
+
+	body: ::reqwest::unstable::async::Decoder,
+	url: ::reqwest::Url,
+}
+
+impl Stream for Download {
+	type Item = ::reqwest::unstable::async::Chunk;
+	type Error = ::Error;
+
+	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+		self.body.poll().map_err(|err| ::ErrorKind::HTTP(self.url.clone(), err).into())
+	}
+}
+
 /// A single page of a paged response.
 #[derive(Debug, ::serde_derive::Deserialize)]
 struct PagedResponse<T> {
@@ -165,11 +239,11 @@ enum Either<A, B> {
 	B(B),
 }
 
-impl<A, B> Stream for Either<A, B> where A: Stream, B: Stream<Item = A::Item, Error = A::Error> {
+impl<A, B> Future for Either<A, B> where A: Future, B: Future<Item = A::Item, Error = A::Error> {
 	type Item = A::Item;
 	type Error = A::Error;
 
-	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
 		match *self {
 			Either::A(ref mut a) => a.poll(),
 			Either::B(ref mut b) => b.poll(),
@@ -177,9 +251,12 @@ impl<A, B> Stream for Either<A, B> where A: Stream, B: Stream<Item = A::Item, Er
 	}
 }
 
+/// The maximum number of mod names to put in the name list of a single request, to keep the URL to a reasonable length.
+const GET_MANY_BATCH_SIZE: usize = 50;
+
 lazy_static! {
 	static ref BASE_URL: ::reqwest::Url = "https://mods.factorio.com/".parse().unwrap();
-	static ref MODS_URL: ::reqwest::Url = "https://mods.factorio.com/api/mods?page_size=max".parse().unwrap();
+	static ref MODS_URL: ::reqwest::Url = "https://mods.factorio.com/api/mods".parse().unwrap();
 	static ref LOGIN_URL: ::reqwest::Url = "https://auth.factorio.com/api-login".parse().unwrap();
 }
 
@@ -190,7 +267,7 @@ mod tests {
 
 	fn run_test<T>(test: T) where for<'r> T: FnOnce(&'r API) -> Box<Future<Item = (), Error = ::Error> + 'r> {
 		let mut core = ::tokio_core::reactor::Core::new().unwrap();
-		let api = API::new(None, core.handle()).unwrap();
+		let api = API::new(None, Default::default(), core.handle()).unwrap();
 		let result = test(&api);
 		core.run(result).unwrap();
 	}
@@ -249,4 +326,35 @@ mod tests {
 				assert_eq!(mod_.title.0, "Bob's Functions Library mod");
 			})));
 	}
+
+	#[test]
+	fn get_full() {
+		let mod_name = ::factorio_mods_common::ModName("boblibrary".to_string());
+
+		run_test(|api| Box::new(
+			api.get_full(&mod_name)
+			.map(|mod_| {
+				println!("{:?}", mod_);
+				assert_eq!(mod_.title.0, "Bob's Functions Library mod");
+				assert!(mod_.releases.iter().all(|release| release.info_json.dependencies.is_some()));
+			})));
+	}
+
+	#[test]
+	fn get_many() {
+		let mod_names = vec![
+			::factorio_mods_common::ModName("boblibrary".to_string()),
+			::factorio_mods_common::ModName("bobplates".to_string()),
+			::factorio_mods_common::ModName("arnavion's awesome mod".to_string()),
+		];
+
+		run_test(|api| Box::new(
+			api.get_many(&mod_names)
+			.map(|mods| {
+				println!("{:?}", mods);
+				let mut names: Vec<_> = mods.into_iter().map(|mod_| mod_.name.0).collect();
+				names.sort();
+				assert_eq!(names, vec!["boblibrary", "bobplates"]);
+			})));
+	}
 }
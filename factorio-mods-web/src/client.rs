@@ -8,6 +8,8 @@ use ::futures::{ future, Future };
 #[derive(Debug)]
 pub struct Client {
 	inner: ::reqwest::unstable::async::Client,
+	retry_policy: ::RetryPolicy,
+	handle: ::tokio_core::reactor::Handle,
 }
 
 impl Client {
@@ -15,6 +17,7 @@ impl Client {
 	#[cfg_attr(feature = "cargo-clippy", allow(needless_pass_by_value))] // reqwest::ClientBuilder::build violates API guidelines. Don't perpetuate it.
 	pub fn new(
 		builder: Option<::reqwest::unstable::async::ClientBuilder>,
+		retry_policy: ::RetryPolicy,
 		handle: ::tokio_core::reactor::Handle,
 	) -> ::Result<Self> {
 		let mut builder = builder.unwrap_or_else(::reqwest::unstable::async::ClientBuilder::new);
@@ -39,29 +42,43 @@ impl Client {
 			.build(&handle)
 			.map_err(::ErrorKind::CreateClient)?;
 
-		Ok(Client { inner })
+		Ok(Client { inner, retry_policy, handle })
 	}
 
 	/// GETs the given URL using the given client, and deserializes the response as a JSON object.
 	pub fn get_object<T>(&self, url: ::reqwest::Url) -> impl Future<Item = (T, ::reqwest::Url), Error = ::Error> + 'static
 		where T: ::serde::de::DeserializeOwned + 'static {
 
-		let mut builder = self.inner.get(url.clone());
+		let inner = self.inner.clone();
+		let future = self.send_with_retry(url.clone(), move || {
+			let mut builder = inner.get(url.clone());
+			builder.header(::reqwest::header::Accept::json());
+			builder
+		});
 
 		::async_block! {
-			builder.header(::reqwest::header::Accept::json());
-			let (response, url) = ::await!(send(builder, url))?;
+			let (response, url) = ::await!(future)?;
 			Ok(::await!(json(response, url))?)
 		}
 	}
 
 	/// GETs the given URL using the given client, and returns an application/zip response.
-	pub fn get_zip(&self, url: ::reqwest::Url) -> impl Future<Item = (::reqwest::unstable::async::Response, ::reqwest::Url), Error = ::Error> + 'static {
-		let mut builder = self.inner.get(url.clone());
+	///
+	/// If `range_start` is set, only the contents from that offset onwards are requested. The server may ignore this and send the whole file,
+	/// so check whether the response status is 206 Partial Content.
+	pub fn get_zip(&self, url: ::reqwest::Url, range_start: Option<u64>) -> impl Future<Item = (::reqwest::unstable::async::Response, ::reqwest::Url), Error = ::Error> + 'static {
+		let inner = self.inner.clone();
+		let future = self.send_with_retry(url.clone(), move || {
+			let mut builder = inner.get(url.clone());
+			builder.header(ACCEPT_APPLICATION_ZIP.clone());
+			if let Some(range_start) = range_start {
+				builder.header(::reqwest::header::Range::Bytes(vec![::reqwest::header::ByteRangeSpec::AllFrom(range_start)]));
+			}
+			builder
+		});
 
 		::async_block! {
-			builder.header(ACCEPT_APPLICATION_ZIP.clone());
-			let (response, url) = ::await!(send(builder, url))?;
+			let (response, url) = ::await!(future)?;
 			let url = expect_content_type(&response, url, &APPLICATION_ZIP)?;
 			Ok((response, url))
 		}
@@ -90,10 +107,60 @@ impl Client {
 			.header(::reqwest::header::ContentLength(body.len() as u64))
 			.body(body);
 
-			let (response, url) = ::await!(send(builder, url))?;
+			// Never retried, since logging in is not idempotent.
+			let (response, url) = match ::await!(send(builder, url))? {
+				Attempt::Done(response, url) => (response, url),
+				Attempt::Retry(err, _) => return Err(err),
+			};
+
 			Ok(::await!(json(response, url))?)
 		})
 	}
+
+	/// Sends the request created by `make_builder`, and sends it again according to the retry policy if it fails with a transient error.
+	fn send_with_retry<F>(&self, url: ::reqwest::Url, make_builder: F) -> impl Future<Item = (::reqwest::unstable::async::Response, ::reqwest::Url), Error = ::Error> + 'static
+		where F: Fn() -> ::reqwest::unstable::async::RequestBuilder + 'static {
+
+		let retry_policy = self.retry_policy;
+		let handle = self.handle.clone();
+
+		::async_block! {
+			let mut retry = 0;
+
+			loop {
+				let (err, retry_after) = match ::await!(send(make_builder(), url.clone()))? {
+					Attempt::Done(response, url) => return Ok((response, url)),
+					Attempt::Retry(err, retry_after) => (err, retry_after),
+				};
+
+				if retry >= retry_policy.max_retries {
+					return Err(err);
+				}
+
+				retry += 1;
+
+				let delay = retry_after.unwrap_or_else(|| retry_policy.delay(retry));
+
+				let timeout = match ::tokio_core::reactor::Timeout::new(delay, &handle) {
+					Ok(timeout) => timeout,
+					Err(err) => bail!(::ErrorKind::Timer(url, err)),
+				};
+
+				if let Err(err) = ::await!(timeout) {
+					bail!(::ErrorKind::Timer(url, err));
+				}
+			}
+		}
+	}
+}
+
+/// The result of a single attempt to send a request.
+enum Attempt {
+	/// The request succeeded.
+	Done(::reqwest::unstable::async::Response, ::reqwest::Url),
+
+	/// The request failed with a transient error, so it can be retried. If the server specified how long to wait before retrying, that is also returned.
+	Retry(::Error, Option<::std::time::Duration>),
 }
 
 lazy_static! {
@@ -115,7 +182,7 @@ struct LoginFailureResponse {
 fn send(
 	mut builder: ::reqwest::unstable::async::RequestBuilder,
 	url: ::reqwest::Url,
-) -> impl Future<Item = (::reqwest::unstable::async::Response, ::reqwest::Url), Error = ::Error> + 'static {
+) -> impl Future<Item = Attempt, Error = ::Error> + 'static {
 	::async_block! {
 		let is_whitelisted_host = match url.host_str() {
 			Some(host) if WHITELISTED_HOSTS.contains(host) => true,
@@ -126,11 +193,28 @@ fn send(
 
 		let response = match ::await!(builder.send()) {
 			Ok(response) => response,
-			Err(err) => bail!(::ErrorKind::HTTP(url, err)),
+			Err(err) => return Ok(Attempt::Retry(::ErrorKind::HTTP(url, err).into(), None)),
 		};
 
 		match response.status() {
-			::reqwest::StatusCode::Ok => Ok((response, url)),
+			::reqwest::StatusCode::Ok |
+			::reqwest::StatusCode::PartialContent => Ok(Attempt::Done(response, url)),
+
+			code @ ::reqwest::StatusCode::TooManyRequests |
+			code @ ::reqwest::StatusCode::ServiceUnavailable => {
+				let retry_after = response.headers().get().and_then(|retry_after| match *retry_after {
+					::reqwest::header::RetryAfter::Delay(delay) => Some(delay),
+					::reqwest::header::RetryAfter::DateTime(date_time) =>
+						::std::time::SystemTime::from(date_time).duration_since(::std::time::SystemTime::now()).ok(),
+				});
+
+				Ok(Attempt::Retry(::ErrorKind::StatusCode(url, code).into(), retry_after))
+			},
+
+			code @ ::reqwest::StatusCode::InternalServerError |
+			code @ ::reqwest::StatusCode::BadGateway |
+			code @ ::reqwest::StatusCode::GatewayTimeout =>
+				Ok(Attempt::Retry(::ErrorKind::StatusCode(url, code).into(), None)),
 
 			::reqwest::StatusCode::Unauthorized => {
 				let (object, _): (LoginFailureResponse, _) = ::await!(json(response, url))?;
@@ -39,6 +39,12 @@ pub enum ErrorKind {
 	#[error_chain(display = const("Host {0} is not whitelisted"))]
 	NotWhitelistedHost(::reqwest::Url),
 
+	/// Could not create a timer to wait before retrying a request
+	#[error_chain(custom)]
+	#[error_chain(display = const("Could not create timer to retry request to URL {0}"))]
+	#[error_chain(cause = |_, err| err)]
+	Timer(::reqwest::Url, ::std::io::Error),
+
 	/// Could not serialize HTTP POST request body
 	#[error_chain(custom)]
 	#[error_chain(display = const("Could not serialize request body for URL {0}"))]
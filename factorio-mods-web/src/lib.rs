@@ -19,6 +19,7 @@ extern crate futures_await as futures;
 extern crate itertools;
 #[macro_use]
 extern crate lazy_static;
+extern crate rand;
 pub extern crate reqwest;
 extern crate serde;
 extern crate serde_derive;
@@ -28,12 +29,18 @@ pub extern crate tokio_core;
 use futures::prelude::{ async_block, async_stream_block, await, stream_yield };
 
 mod api;
-pub use api::{ API };
+pub use api::{ API, Download };
 
 mod client;
 
 mod error;
 pub use error::{ Error, ErrorKind, Result };
 
+mod query;
+pub use query::{ SearchQuery, SortField, SortOrder };
+
+mod retry;
+pub use retry::RetryPolicy;
+
 mod types;
 pub use types::*;
@@ -0,0 +1,157 @@
+/// A query for `API::search_query`
+///
+/// Paging, sorting, the name list and the game version are handled by the mods portal. The search text is matched locally against the
+/// name, title, owners and summary of each mod, since the mods portal does not support text search.
+#[derive(Clone, Debug, Default)]
+pub struct SearchQuery {
+	text: String,
+	page: Option<u64>,
+	page_size: Option<u64>,
+	sort: Option<(SortField, SortOrder)>,
+	namelist: Vec<::factorio_mods_common::ModName>,
+	game_version: Option<String>,
+}
+
+/// The field to sort search results by.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SortField {
+	/// The name of the mod.
+	Name,
+
+	/// The time the mod was created.
+	CreatedAt,
+
+	/// The time the mod was last updated.
+	UpdatedAt,
+}
+
+/// The order to sort search results in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SortOrder {
+	/// Ascending order.
+	Ascending,
+
+	/// Descending order.
+	Descending,
+}
+
+impl SearchQuery {
+	/// Creates a query that matches mods whose name, title, owners or summary contain the given text. An empty string matches all mods.
+	pub fn new<S>(text: S) -> Self where S: Into<String> {
+		SearchQuery {
+			text: text.into().to_lowercase(),
+			..Default::default()
+		}
+	}
+
+	/// Only returns the given page of results, instead of all pages. Pages start at 1.
+	pub fn page(mut self, page: u64) -> Self {
+		self.page = Some(page);
+		self
+	}
+
+	/// Sets the number of results per page. The default is the maximum page size allowed by the mods portal.
+	pub fn page_size(mut self, page_size: u64) -> Self {
+		self.page_size = Some(page_size);
+		self
+	}
+
+	/// Sorts the results by the given field in the given order.
+	pub fn sort(mut self, field: SortField, order: SortOrder) -> Self {
+		self.sort = Some((field, order));
+		self
+	}
+
+	/// Only returns mods with the given names.
+	pub fn namelist<I>(mut self, names: I) -> Self where I: IntoIterator<Item = ::factorio_mods_common::ModName> {
+		self.namelist.extend(names);
+		self
+	}
+
+	/// Only returns mods whose latest release supports the given game version, such as `"0.16"`
+	pub fn game_version<S>(mut self, game_version: S) -> Self where S: Into<String> {
+		self.game_version = Some(game_version.into());
+		self
+	}
+
+	/// Returns whether only a single page of results was requested.
+	pub(crate) fn is_single_page(&self) -> bool {
+		self.page.is_some()
+	}
+
+	/// Constructs the URL of the first page of results for this query.
+	pub(crate) fn url(&self, mods_url: &::reqwest::Url) -> ::reqwest::Url {
+		let mut url = mods_url.clone();
+
+		{
+			let mut query_pairs = url.query_pairs_mut();
+
+			if let Some(page) = self.page {
+				query_pairs.append_pair("page", &page.to_string());
+			}
+
+			match self.page_size {
+				Some(page_size) => query_pairs.append_pair("page_size", &page_size.to_string()),
+				None => query_pairs.append_pair("page_size", "max"),
+			};
+
+			if let Some((field, order)) = self.sort {
+				query_pairs.append_pair("sort", match field {
+					SortField::Name => "name",
+					SortField::CreatedAt => "created_at",
+					SortField::UpdatedAt => "updated_at",
+				});
+
+				query_pairs.append_pair("sort_order", match order {
+					SortOrder::Ascending => "asc",
+					SortOrder::Descending => "desc",
+				});
+			}
+
+			for name in &self.namelist {
+				query_pairs.append_pair("namelist", &name.0);
+			}
+
+			if let Some(ref game_version) = self.game_version {
+				query_pairs.append_pair("version", game_version);
+			}
+		}
+
+		url
+	}
+
+	/// Returns whether the given mod matches the search text of this query.
+	pub(crate) fn matches(&self, mod_: &::SearchResponseMod) -> bool {
+		let text = &self.text;
+
+		text.is_empty() ||
+		mod_.name.0.to_lowercase().contains(text) ||
+		mod_.title.0.to_lowercase().contains(text) ||
+		mod_.owner.iter().any(|owner| owner.0.to_lowercase().contains(text)) ||
+		mod_.summary.0.to_lowercase().contains(text)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn url() {
+		let mods_url: ::reqwest::Url = "https://mods.factorio.com/api/mods".parse().unwrap();
+
+		assert_eq!(
+			SearchQuery::new("").url(&mods_url).as_str(),
+			"https://mods.factorio.com/api/mods?page_size=max");
+
+		assert_eq!(
+			SearchQuery::new("bob")
+			.page(2)
+			.page_size(25)
+			.sort(SortField::UpdatedAt, SortOrder::Descending)
+			.namelist(vec![::factorio_mods_common::ModName("boblibrary".to_string()), ::factorio_mods_common::ModName("bobplates".to_string())])
+			.game_version("0.16")
+			.url(&mods_url).as_str(),
+			"https://mods.factorio.com/api/mods?page=2&page_size=25&sort=updated_at&sort_order=desc&namelist=boblibrary&namelist=bobplates&version=0.16");
+	}
+}
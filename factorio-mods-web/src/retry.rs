@@ -0,0 +1,80 @@
+/// How failed requests are retried.
+///
+/// Requests that fail with a connection error or a 429, 500, 502, 503 or 504 status code are retried with exponential backoff and jitter.
+/// If a 429 or 503 response has a Retry-After header, the delay it specifies is used instead.
+///
+/// Only idempotent requests, such as getting mod information and downloading releases, are retried. Logging in is never retried.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+	/// The maximum number of times a request is retried after the first attempt. 0 disables retries.
+	pub max_retries: u32,
+
+	/// The delay before the first retry. The delay doubles with every subsequent retry.
+	pub initial_delay: ::std::time::Duration,
+
+	/// The maximum delay between retries, not counting delays requested by the server with Retry-After.
+	pub max_delay: ::std::time::Duration,
+}
+
+impl Default for RetryPolicy {
+	fn default() -> Self {
+		RetryPolicy {
+			max_retries: 3,
+			initial_delay: ::std::time::Duration::from_secs(1),
+			max_delay: ::std::time::Duration::from_secs(60),
+		}
+	}
+}
+
+impl RetryPolicy {
+	/// Returns how long to wait before the given retry, starting from 1.
+	///
+	/// The delay is picked randomly from the upper half of the exponential backoff, so that many clients that failed at the same time
+	/// don't all retry at the same time.
+	pub(crate) fn delay(&self, retry: u32) -> ::std::time::Duration {
+		let initial_delay = millis(self.initial_delay);
+		let max_delay = millis(self.max_delay);
+
+		let backoff =
+			2u64.checked_pow(retry.saturating_sub(1))
+			.and_then(|factor| initial_delay.checked_mul(factor))
+			.map_or(max_delay, |backoff| backoff.min(max_delay));
+
+		let half = backoff / 2;
+		let jitter = ::rand::Rng::gen_range(&mut ::rand::thread_rng(), 0, half + 1);
+
+		::std::time::Duration::from_millis(backoff - half + jitter)
+	}
+}
+
+fn millis(duration: ::std::time::Duration) -> u64 {
+	duration.as_secs() * 1000 + u64::from(duration.subsec_nanos() / 1_000_000)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn delay() {
+		let retry_policy = RetryPolicy {
+			max_retries: 10,
+			initial_delay: ::std::time::Duration::from_secs(1),
+			max_delay: ::std::time::Duration::from_secs(10),
+		};
+
+		for _ in 0..100 {
+			let delay = retry_policy.delay(1);
+			assert!(delay >= ::std::time::Duration::from_millis(500) && delay <= ::std::time::Duration::from_secs(1));
+
+			let delay = retry_policy.delay(3);
+			assert!(delay >= ::std::time::Duration::from_secs(2) && delay <= ::std::time::Duration::from_secs(4));
+
+			let delay = retry_policy.delay(10);
+			assert!(delay >= ::std::time::Duration::from_secs(5) && delay <= ::std::time::Duration::from_secs(10));
+
+			let delay = retry_policy.delay(100);
+			assert!(delay >= ::std::time::Duration::from_secs(5) && delay <= ::std::time::Duration::from_secs(10));
+		}
+	}
+}
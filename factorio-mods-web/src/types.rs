@@ -18,7 +18,7 @@ pub struct DateTime(pub String);
 )]
 pub struct DownloadCount(pub u64);
 
-/// A mod object returned by `API::get`.
+/// A mod object returned by `API::get` and `API::get_many`.
 #[derive(Clone, Debug, PartialEq, ::serde_derive::Deserialize)]
 pub struct Mod {
 	/// The name of the mod.
@@ -41,6 +41,116 @@ pub struct Mod {
 	pub downloads_count: DownloadCount,
 }
 
+/// A mod object with all its details, returned by `API::get_full`.
+#[derive(Clone, Debug, PartialEq, ::serde_derive::Deserialize)]
+pub struct ModFull {
+	/// The name of the mod.
+	pub name: ::factorio_mods_common::ModName,
+
+	/// The title of the mod.
+	pub title: ::factorio_mods_common::ModTitle,
+
+	/// The authors of the mod.
+	#[serde(deserialize_with = "::factorio_mods_common::deserialize_string_or_seq_string")]
+	pub owner: Vec<::factorio_mods_common::AuthorName>,
+
+	/// A short summary of the mod.
+	pub summary: ModSummary,
+
+	/// All the releases of the mod, including their dependencies.
+	pub releases: Vec<ModRelease>,
+
+	/// The number of times the mod has been downloaded.
+	pub downloads_count: DownloadCount,
+
+	/// A longer description of the mod.
+	#[serde(default)]
+	pub description: Option<::factorio_mods_common::ModDescription>,
+
+	/// The changelog of the mod.
+	#[serde(default)]
+	pub changelog: Option<ModChangelog>,
+
+	/// The category of the mod.
+	#[serde(default)]
+	pub category: Option<ModCategory>,
+
+	/// The tags of the mod.
+	#[serde(default)]
+	pub tags: Vec<ModTag>,
+
+	/// The URL of the homepage of the mod.
+	#[serde(default)]
+	pub homepage: Option<::factorio_mods_common::Url>,
+
+	/// The path of the mod's repository on GitHub, of the form `owner/repository`
+	#[serde(default)]
+	pub github_path: Option<GitHubPath>,
+
+	/// The license of the mod.
+	#[serde(default)]
+	pub license: Option<ModLicense>,
+
+	/// The URL of the thumbnail of the mod, relative to the mods portal.
+	#[serde(default)]
+	pub thumbnail: Option<::factorio_mods_common::Url>,
+
+	/// The date and time at which the mod was created.
+	pub created_at: DateTime,
+
+	/// The date and time at which the mod was last updated.
+	#[serde(default)]
+	pub updated_at: Option<DateTime>,
+}
+
+/// The changelog of a mod.
+#[derive(
+	Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd,
+	::derive_struct::newtype_display,
+	::serde_derive::Deserialize,
+)]
+pub struct ModChangelog(pub String);
+
+/// The category of a mod.
+#[derive(
+	Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd,
+	::derive_struct::newtype_display,
+	::serde_derive::Deserialize,
+)]
+pub struct ModCategory(pub String);
+
+/// The path of a GitHub repository, of the form `owner/repository`
+#[derive(
+	Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd,
+	::derive_struct::newtype_display,
+	::serde_derive::Deserialize,
+)]
+pub struct GitHubPath(pub String);
+
+/// A tag of a mod.
+#[derive(Clone, Debug, PartialEq, ::serde_derive::Deserialize)]
+pub struct ModTag {
+	/// The name of the tag.
+	pub name: String,
+
+	/// The title of the tag.
+	pub title: String,
+}
+
+/// The license of a mod.
+#[derive(Clone, Debug, PartialEq, ::serde_derive::Deserialize)]
+pub struct ModLicense {
+	/// The name of the license.
+	pub name: String,
+
+	/// The title of the license.
+	pub title: String,
+
+	/// The URL of the text of the license.
+	#[serde(default)]
+	pub url: Option<::factorio_mods_common::Url>,
+}
+
 /// The summary of a mod.
 #[derive(
 	Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd,
@@ -77,6 +187,10 @@ pub struct ModRelease {
 pub struct ModReleaseInfo {
 	/// The versions of the game supported by the mod release.
 	pub factorio_version: ::factorio_mods_common::ModVersionReq,
+
+	/// The dependencies of the mod release. Only returned by `API::get_full`
+	#[serde(default)]
+	pub dependencies: Option<Vec<::factorio_mods_common::Dependency>>,
 }
 
 /// The hash of a mod release file.
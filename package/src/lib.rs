@@ -172,7 +172,7 @@ pub fn compute_solution<I>(
 					package.dependencies().into_iter()
 					.filter(|dep| dep.required())
 					.all(|dep|
-						name_to_node_indices.get_vec(dep.name()).unwrap().into_iter()
+						name_to_node_indices.get_vec(dep.name()).into_iter().flat_map(|node_indices| node_indices)
 						.any(|&dep_node_index| dep.version().as_ref().matches(graph[dep_node_index].version().as_ref())));
 
 				!keep
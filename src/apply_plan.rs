@@ -0,0 +1,49 @@
+use ::futures::{ future, Future, IntoFuture };
+
+pub struct SubCommand;
+
+impl ::util::SubCommand for SubCommand {
+	fn build_subcommand<'a>(&self, subcommand: ::clap::App<'a, 'a>) -> ::clap::App<'a, 'a> {
+		clap_app!(@app (subcommand)
+			(about: "Apply a plan previously saved with `--dry-run --save-plan`.")
+			(@arg plan: +required index(1) "path of the plan file"))
+	}
+
+	fn run<'a>(
+		&'a self,
+		matches: &'a ::clap::ArgMatches<'a>,
+		local_api: ::Result<&'a ::factorio_mods_local::API>,
+		_: ::Result<&'a ::factorio_mods_web::API>,
+		global_options: ::util::GlobalOptions<'a>,
+	) -> Box<Future<Item = (), Error = ::Error> + 'a> {
+		#[cfg_attr(feature = "cargo-clippy", allow(unit_arg))]
+		let result: ::Result<_> = do catch {
+			let plan_file_path = ::std::path::Path::new(matches.value_of("plan").unwrap());
+
+			let local_api = local_api?;
+
+			let mut config = ::config::Config::load(local_api, global_options.wait_for_lock)?;
+
+			let plan = ::plan::Plan::load(plan_file_path)?;
+
+			plan.check(local_api)?;
+
+			plan.print();
+
+			if global_options.dry_run {
+				return Box::new(future::ok(()));
+			}
+
+			if !plan.is_empty() && !::util::prompt_continue(global_options.prompt_override)? {
+				return Box::new(future::ok(()));
+			}
+
+			plan.apply(local_api, global_options)?;
+
+			config.mods = plan.reqs.into_iter().collect();
+			config.save()?;
+		};
+
+		Box::new(result.into_future())
+	}
+}
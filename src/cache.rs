@@ -0,0 +1,370 @@
+use ::futures::{ Future, IntoFuture };
+
+use ::ResultExt;
+
+pub struct SubCommand;
+
+impl ::util::SubCommand for SubCommand {
+	fn build_subcommand<'a>(&self, subcommand: ::clap::App<'a, 'a>) -> ::clap::App<'a, 'a> {
+		clap_app!(@app (subcommand)
+			(about: "Manage the cache of downloaded mods.")
+			(@setting SubcommandRequiredElseHelp)
+			(@subcommand list =>
+				(about: "List cached mod releases, their sizes and whether they're installed.\n\n\
+				         Unpacked mods that `fac dedupe` moved into the cache are listed along with the releases."))
+			(@subcommand prune =>
+				(about: "Delete old cached mod releases and partial downloads.\n\n\
+				         The newest releases of each mod are kept, as well as installed releases and \
+				         the newest cached release that satisfies each requirement in the config.")
+				(@arg keep: --keep +takes_value default_value("2") "number of newest releases of each mod to keep"))
+			(@subcommand clean =>
+				(about: "Delete all cached mod releases, unpacked mods and partial downloads, except those that installed mods are symlinked to.")
+				(@arg force: --force "delete everything even if the local Factorio installation can't be found, \
+				                      in which case cached mods that installed mods are symlinked to are deleted too"))
+			(@subcommand path =>
+				(about: "Print the path of the cache directory.")))
+	}
+
+	fn run<'a>(
+		&'a self,
+		matches: &'a ::clap::ArgMatches<'a>,
+		local_api: ::Result<&'a ::factorio_mods_local::API>,
+		_: ::Result<&'a ::factorio_mods_web::API>,
+		global_options: ::util::GlobalOptions<'a>,
+	) -> Box<Future<Item = (), Error = ::Error> + 'a> {
+		#[cfg_attr(feature = "cargo-clippy", allow(unit_arg))]
+		let result: ::Result<_> = do catch {
+			let cache_directory = ::config::cache_directory()?;
+
+			match matches.subcommand() {
+				("list", _) => list(&cache_directory, local_api?, global_options)?,
+
+				("prune", Some(matches)) => {
+					let keep = matches.value_of("keep").unwrap();
+					let keep: usize = keep.parse().chain_err(|| format!(r#"Could not parse "{}" as a number of releases"#, keep))?;
+					prune(&cache_directory, local_api?, keep, global_options)?
+				},
+
+				// The local API is only needed to find symlinked mods. Without it, those can't be protected, so only delete everything if asked to.
+				("clean", Some(matches)) => {
+					let local_api = match local_api {
+						Ok(local_api) => Some(local_api),
+						Err(_) if matches.is_present("force") => None,
+						Err(err) => Err(err).chain_err(|| "Could not find installed mods that are symlinked to the cache. Use --force to delete everything anyway.")?,
+					};
+					clean(&cache_directory, local_api, global_options)?
+				},
+
+				("path", _) => println!("{}", cache_directory.display()),
+
+				_ => unreachable!(),
+			}
+		};
+
+		Box::new(result.into_future())
+	}
+}
+
+/// A file in the cache directory, or a directory in its `unpacked` subdirectory.
+#[derive(Debug)]
+struct CacheEntry {
+	path: ::std::path::PathBuf,
+
+	/// The size of the file, or the total size of the files in the directory.
+	len: u64,
+
+	kind: CacheEntryKind,
+
+	/// Whether this is an unpacked mod that `fac dedupe` moved into the cache, rather than a zip.
+	unpacked: bool,
+}
+
+#[derive(Debug)]
+enum CacheEntryKind {
+	/// A downloaded mod release.
+	Release(::factorio_mods_common::ModName, ::factorio_mods_common::ReleaseVersion),
+
+	/// A partially downloaded mod release.
+	Partial,
+
+	/// A zip file or unpacked directory that could not be parsed as a mod release, such as a download that was interrupted before it was verified.
+	Unrecognized,
+}
+
+/// Returns the subdirectory of the cache directory that `fac dedupe` moves unpacked mods into.
+pub fn unpacked_directory(cache_directory: &::std::path::Path) -> ::std::path::PathBuf {
+	cache_directory.join("unpacked")
+}
+
+/// Returns the subdirectory of the cache directory that `fac dedupe` moves zipped mods into when the cache already has a different file
+/// for the same release.
+pub fn dedupe_directory(cache_directory: &::std::path::Path) -> ::std::path::PathBuf {
+	cache_directory.join("dedupe")
+}
+
+/// Enumerates the mod releases and partial downloads in the cache directory, and the unpacked mods in its `unpacked` subdirectory.
+///
+/// Other files, such as the search index, are not included.
+fn entries(cache_directory: &::std::path::Path) -> ::Result<Vec<CacheEntry>> {
+	let mut result = vec![];
+
+	for directory_entry in read_dir(cache_directory)? {
+		let directory_entry = directory_entry.chain_err(|| format!("Could not read cache directory {}", cache_directory.display()))?;
+		let path = directory_entry.path();
+
+		let metadata = directory_entry.metadata().chain_err(|| format!("Could not read metadata of {}", path.display()))?;
+		if !metadata.is_file() {
+			continue;
+		}
+
+		let kind = match path.extension().and_then(|extension| extension.to_str()) {
+			Some("zip") => match ::factorio_mods_local::InstalledMod::parse(path.clone()) {
+				Ok(release) => CacheEntryKind::Release(release.info.name, release.info.version),
+				Err(_) => CacheEntryKind::Unrecognized,
+			},
+
+			Some("new") => CacheEntryKind::Partial,
+
+			_ => continue,
+		};
+
+		result.push(CacheEntry { path, len: metadata.len(), kind, unpacked: false });
+	}
+
+	let unpacked_directory = unpacked_directory(cache_directory);
+
+	for directory_entry in read_dir(&unpacked_directory)? {
+		let directory_entry = directory_entry.chain_err(|| format!("Could not read directory {}", unpacked_directory.display()))?;
+		let path = directory_entry.path();
+
+		let metadata = directory_entry.metadata().chain_err(|| format!("Could not read metadata of {}", path.display()))?;
+		if !metadata.is_dir() {
+			continue;
+		}
+
+		let kind = match ::factorio_mods_local::InstalledMod::parse(path.clone()) {
+			Ok(release) => CacheEntryKind::Release(release.info.name, release.info.version),
+			Err(_) => CacheEntryKind::Unrecognized,
+		};
+
+		let len = directory_len(&path)?;
+
+		result.push(CacheEntry { path, len, kind, unpacked: true });
+	}
+
+	result.sort_by(|entry1, entry2| match (&entry1.kind, &entry2.kind) {
+		(&CacheEntryKind::Release(ref name1, ref version1), &CacheEntryKind::Release(ref name2, ref version2)) =>
+			name1.cmp(name2).then_with(|| version1.cmp(version2).reverse()).then_with(|| entry1.unpacked.cmp(&entry2.unpacked)),
+		(&CacheEntryKind::Release(..), _) => ::std::cmp::Ordering::Less,
+		(_, &CacheEntryKind::Release(..)) => ::std::cmp::Ordering::Greater,
+		_ => entry1.path.cmp(&entry2.path),
+	});
+
+	Ok(result)
+}
+
+/// Reads the given directory. A directory that doesn't exist is treated as empty.
+fn read_dir(directory: &::std::path::Path) -> ::Result<Box<Iterator<Item = ::std::io::Result<::std::fs::DirEntry>>>> {
+	match ::std::fs::read_dir(directory) {
+		Ok(directory_entries) => Ok(Box::new(directory_entries)),
+		Err(ref err) if err.kind() == ::std::io::ErrorKind::NotFound => Ok(Box::new(::std::iter::empty())),
+		Err(err) => Err(err).chain_err(|| format!("Could not read directory {}", directory.display())),
+	}
+}
+
+/// Returns the total size of the files in the given directory and its subdirectories.
+fn directory_len(directory: &::std::path::Path) -> ::Result<u64> {
+	let mut result = 0;
+
+	for directory_entry in ::std::fs::read_dir(directory).chain_err(|| format!("Could not read directory {}", directory.display()))? {
+		let directory_entry = directory_entry.chain_err(|| format!("Could not read directory {}", directory.display()))?;
+		let path = directory_entry.path();
+
+		let metadata = directory_entry.metadata().chain_err(|| format!("Could not read metadata of {}", path.display()))?;
+		result +=
+			if metadata.is_dir() { directory_len(&path)? }
+			else { metadata.len() };
+	}
+
+	Ok(result)
+}
+
+/// Returns the names and versions of all installed mods.
+fn installed_releases(
+	local_api: &::factorio_mods_local::API,
+) -> ::Result<::std::collections::HashSet<(::factorio_mods_common::ModName, ::factorio_mods_common::ReleaseVersion)>> {
+	let installed_mods: ::Result<_> =
+		local_api.installed_mods().chain_err(|| "Could not enumerate installed mods")?
+		.map(|mod_|
+			mod_
+			.map(|mod_| (mod_.info.name, mod_.info.version))
+			.chain_err(|| "Could not process an installed mod"))
+		.collect();
+	installed_mods.chain_err(|| "Could not enumerate installed mods")
+}
+
+fn list(cache_directory: &::std::path::Path, local_api: &::factorio_mods_local::API, global_options: ::util::GlobalOptions) -> ::Result<()> {
+	let entries = entries(cache_directory)?;
+	let installed_releases = installed_releases(local_api)?;
+
+	let is_installed = |entry: &CacheEntry| match entry.kind {
+		CacheEntryKind::Release(ref name, ref version) => installed_releases.contains(&(name.clone(), version.clone())),
+		_ => false,
+	};
+
+	if let ::util::OutputFormat::Json = global_options.output_format {
+		let records: Vec<_> =
+			entries.iter()
+			.map(|entry| {
+				let (name, version) = match entry.kind {
+					CacheEntryKind::Release(ref name, ref version) => (Some(name), Some(version)),
+					_ => (None, None),
+				};
+
+				::output::CachedReleaseRecord {
+					path: &entry.path,
+					size: entry.len,
+					name,
+					version,
+					partial: match entry.kind { CacheEntryKind::Partial => true, _ => false },
+					unpacked: entry.unpacked,
+					installed: is_installed(entry),
+				}
+			})
+			.collect();
+		return ::output::print_json(&records);
+	}
+
+	println!("Cache directory: {}", cache_directory.display());
+
+	if entries.is_empty() {
+		println!("No cached mods.");
+		return Ok(());
+	}
+
+	let mut previous_name = None;
+
+	for entry in &entries {
+		match entry.kind {
+			CacheEntryKind::Release(ref name, ref version) => {
+				if previous_name != Some(name) {
+					println!("    {}", name);
+					previous_name = Some(name);
+				}
+
+				println!(
+					"        {:-9} {:>10}{}{}",
+					version, ::util::format_bytes(entry.len),
+					if entry.unpacked { " (unpacked)" } else { "" },
+					if is_installed(entry) { " (installed)" } else { "" });
+			},
+
+			CacheEntryKind::Partial =>
+				println!("    {} {} (partial download)", entry.path.display(), ::util::format_bytes(entry.len)),
+
+			CacheEntryKind::Unrecognized =>
+				println!("    {} {} (not a valid mod)", entry.path.display(), ::util::format_bytes(entry.len)),
+		}
+	}
+
+	println!("Total: {} in {} files", ::util::format_bytes(entries.iter().map(|entry| entry.len).sum()), entries.len());
+
+	Ok(())
+}
+
+fn prune(cache_directory: &::std::path::Path, local_api: &::factorio_mods_local::API, keep: usize, global_options: ::util::GlobalOptions) -> ::Result<()> {
+	// A dry run doesn't delete anything, so it doesn't need to wait for other instances of fac.
+	let config =
+		if global_options.dry_run { ::config::Config::load_read_only(local_api)? }
+		else { ::config::Config::load(local_api, global_options.wait_for_lock)? };
+	let entries = entries(cache_directory)?;
+	let installed_releases = installed_releases(local_api)?;
+
+	let mut kept_per_mod: ::std::collections::HashMap<&::factorio_mods_common::ModName, usize> = Default::default();
+	let mut locked: ::std::collections::HashSet<&::factorio_mods_common::ModName> = Default::default();
+
+	// Entries are sorted newest release first, so the first releases seen of each mod are the ones to keep.
+	let to_remove: Vec<_> =
+		entries.iter()
+		.filter(|entry| match entry.kind {
+			CacheEntryKind::Release(ref name, ref version) => {
+				let kept = kept_per_mod.entry(name).or_insert(0);
+				let mut keep_this = false;
+
+				if *kept < keep {
+					keep_this = true;
+				}
+
+				if installed_releases.contains(&(name.clone(), version.clone())) {
+					keep_this = true;
+				}
+
+				if !locked.contains(name) && config.mods.get(name).map_or(false, |req| req.0.matches(&version.0)) {
+					locked.insert(name);
+					keep_this = true;
+				}
+
+				if keep_this {
+					*kept += 1;
+				}
+
+				!keep_this
+			},
+
+			CacheEntryKind::Partial | CacheEntryKind::Unrecognized => true,
+		})
+		.collect();
+
+	remove(&to_remove, global_options)
+}
+
+fn clean(cache_directory: &::std::path::Path, local_api: Option<&::factorio_mods_local::API>, global_options: ::util::GlobalOptions) -> ::Result<()> {
+	let _lock = ::lock::Lock::acquire(local_api, global_options.wait_for_lock)?;
+
+	let entries = entries(cache_directory)?;
+
+	let mut symlink_targets = ::std::collections::HashSet::new();
+	if let Some(local_api) = local_api {
+		for installed_mod in local_api.installed_mods().chain_err(|| "Could not enumerate installed mods")? {
+			let installed_mod = installed_mod.chain_err(|| "Could not process an installed mod")?;
+			if let ::link::LinkState::Symlink(_) = ::link::LinkState::of(&installed_mod.path)? {
+				if let Ok(target) = installed_mod.path.canonicalize() {
+					symlink_targets.insert(target);
+				}
+			}
+		}
+	}
+
+	let to_remove: Vec<_> =
+		entries.iter()
+		.filter(|entry| entry.path.canonicalize().map_or(true, |path| !symlink_targets.contains(&path)))
+		.collect();
+
+	remove(&to_remove, global_options)
+}
+
+/// Prints the given cache entries and then deletes them, after prompting.
+fn remove(entries: &[&CacheEntry], global_options: ::util::GlobalOptions) -> ::Result<()> {
+	if entries.is_empty() {
+		println!("Nothing to do.");
+		return Ok(());
+	}
+
+	println!("The following files and directories will be deleted:");
+	for entry in entries {
+		println!("    {} ({})", entry.path.display(), ::util::format_bytes(entry.len));
+	}
+	println!();
+	println!("Total: {} in {} files", ::util::format_bytes(entries.iter().map(|entry| entry.len).sum()), entries.len());
+	println!();
+
+	if global_options.dry_run || !::util::prompt_continue(global_options.prompt_override)? {
+		return Ok(());
+	}
+
+	for entry in entries {
+		::link::remove(&entry.path)?;
+	}
+
+	Ok(())
+}
@@ -12,16 +12,42 @@ enum StoredConfig<'a> {
 	V1 {
 		#[serde(serialize_with = "serialize_config_mods")]
 		mods: ::std::borrow::Cow<'a, ::std::collections::HashMap<::factorio_mods_common::ModName, ::factorio_mods_common::ModVersionReq>>,
+
+		#[serde(default)]
+		dev_links: ::std::borrow::Cow<'a, ::std::collections::BTreeMap<::factorio_mods_common::ModName, ::std::path::PathBuf>>,
 	},
 }
 
 #[derive(Debug)]
 pub struct Config {
 	pub mods: ::std::collections::HashMap<::factorio_mods_common::ModName, ::factorio_mods_common::ModVersionReq>,
+
+	/// Mods that are linked into the mods directory from a working copy with `fac dev link`, and the paths of their working copies.
+	pub dev_links: ::std::collections::BTreeMap<::factorio_mods_common::ModName, ::std::path::PathBuf>,
+
+	/// Held for as long as the config is alive, so that no other instance of fac modifies the mods directory until this one has saved its config.
+	/// `None` if the config was loaded with `load_read_only`
+	lock: Option<::lock::Lock>,
 }
 
 impl Config {
-	pub fn load(api: &::factorio_mods_local::API) -> ::Result<Self> {
+	/// Loads the config, after acquiring the lock that prevents other instances of fac from running at the same time.
+	///
+	/// If another instance holds the lock, this waits for it to be released if `wait` is true, and fails otherwise.
+	pub fn load(api: &::factorio_mods_local::API, wait: bool) -> ::Result<Self> {
+		let lock = ::lock::Lock::acquire(Some(api), wait)?;
+		Config::load_inner(api, Some(lock))
+	}
+
+	/// Loads the config without acquiring the lock, for commands that only read it and don't change anything.
+	///
+	/// This works even while another instance of fac is running, but what it reads may be in the middle of being changed.
+	/// The returned config cannot be saved.
+	pub fn load_read_only(api: &::factorio_mods_local::API) -> ::Result<Self> {
+		Config::load_inner(api, None)
+	}
+
+	fn load_inner(api: &::factorio_mods_local::API, lock: Option<::lock::Lock>) -> ::Result<Self> {
 		let user_config_dir = ::appdirs::user_config_dir(Some("fac"), None, false).map_err(|_| "Could not derive path to config directory")?;
 
 		if let Err(err) = ::std::fs::create_dir(&user_config_dir) {
@@ -37,9 +63,11 @@ impl Config {
 		match ::std::fs::File::open(&config_file_path) {
 			Ok(mut file) => {
 				let config: StoredConfig = ::serde_json::from_reader(&mut file).chain_err(|| format!("Could not parse JSON file {}", config_file_path_displayable))?;
-				let StoredConfig::V1 { mods } = config;
+				let StoredConfig::V1 { mods, dev_links } = config;
 				Ok(Config {
 					mods: mods.into_owned(),
+					dev_links: dev_links.into_owned(),
+					lock,
 				})
 			},
 
@@ -56,6 +84,8 @@ impl Config {
 					let mods = installed_mods.chain_err(|| "Could not enumerate installed mods")?;
 					Ok(Config {
 						mods,
+						dev_links: Default::default(),
+						lock,
 					})
 				},
 
@@ -65,6 +95,8 @@ impl Config {
 	}
 
 	pub fn save(&self) -> ::Result<()> {
+		ensure!(self.lock.is_some(), "Could not save the config because it was loaded read-only");
+
 		let user_config_dir = ::appdirs::user_config_dir(Some("fac"), None, false).map_err(|_| "Could not derive path to config directory")?;
 		if let Err(err) = ::std::fs::create_dir(&user_config_dir) {
 			match err.kind() {
@@ -74,20 +106,27 @@ impl Config {
 		}
 
 		let config_file_path = user_config_dir.join("config.json");
-		let config_file_path_displayable = config_file_path.display();
-		let mut config_file = ::std::fs::File::create(&config_file_path).chain_err(|| format!("Could not create config file {}", config_file_path_displayable))?;
 
-		let stored_config = StoredConfig::V1 { mods: ::std::borrow::Cow::Borrowed(&self.mods) };
-		::serde_json::to_writer_pretty(&mut config_file, &stored_config).chain_err(|| format!("Could not write to config file {}", config_file_path_displayable))?;
+		let stored_config = StoredConfig::V1 {
+			mods: ::std::borrow::Cow::Borrowed(&self.mods),
+			dev_links: ::std::borrow::Cow::Borrowed(&self.dev_links),
+		};
+		::factorio_mods_local::write_json_atomic(&config_file_path, ::factorio_mods_local::Backup::Yes, &stored_config)
+		.chain_err(|| format!("Could not write to config file {}", config_file_path.display()))?;
 
 		Ok(())
 	}
 
 	pub fn cache_directory(&self) -> ::Result<::std::path::PathBuf> {
-		Ok(::appdirs::user_cache_dir(Some("fac"), None).map_err(|_| "Could not derive path to cache directory")?)
+		cache_directory()
 	}
 }
 
+/// Returns the directory where downloaded mods and other cached data are stored.
+pub fn cache_directory() -> ::Result<::std::path::PathBuf> {
+	Ok(::appdirs::user_cache_dir(Some("fac"), None).map_err(|_| "Could not derive path to cache directory")?)
+}
+
 fn serialize_config_mods<S>(
 	value: &::std::collections::HashMap<::factorio_mods_common::ModName, ::factorio_mods_common::ModVersionReq>,
 	serializer: S,
@@ -0,0 +1,233 @@
+use ::futures::{ future, Future, IntoFuture };
+
+use ::ResultExt;
+
+pub struct SubCommand;
+
+impl ::util::SubCommand for SubCommand {
+	fn build_subcommand<'a>(&self, subcommand: ::clap::App<'a, 'a>) -> ::clap::App<'a, 'a> {
+		clap_app!(@app (subcommand)
+			(about: "Remove duplicate installed copies of mods.\n\n\
+			         For each mod that is installed more than once, the highest version that satisfies the requirement in the config is kept, \
+			         or the version the game would load if none does. The other copies are moved into the cache rather than deleted."))
+	}
+
+	fn run<'a>(
+		&'a self,
+		_: &'a ::clap::ArgMatches<'a>,
+		local_api: ::Result<&'a ::factorio_mods_local::API>,
+		_: ::Result<&'a ::factorio_mods_web::API>,
+		global_options: ::util::GlobalOptions<'a>,
+	) -> Box<Future<Item = (), Error = ::Error> + 'a> {
+		#[cfg_attr(feature = "cargo-clippy", allow(unit_arg))]
+		let result: ::Result<_> = do catch {
+			let local_api = local_api?;
+
+			let config = ::config::Config::load(local_api, global_options.wait_for_lock)?;
+			let cache_directory = config.cache_directory()?;
+
+			let effective_mods = local_api.effective_mods().chain_err(|| "Could not enumerate installed mods")?;
+
+			let mut kept = vec![];
+			let mut to_move = vec![];
+
+			for (name, versions) in effective_mods {
+				if versions.shadowed.is_empty() {
+					continue;
+				}
+
+				let mut installed_mods = versions.shadowed;
+				installed_mods.insert(0, versions.effective);
+
+				// The link to the working copy of a mod that is being developed is always kept.
+				let linked_index =
+					if config.dev_links.contains_key(&name) {
+						installed_mods.iter().position(|installed_mod| match ::link::LinkState::of(&installed_mod.path) {
+							Ok(::link::LinkState::Symlink(_)) => true,
+							_ => false,
+						})
+					}
+					else {
+						None
+					};
+
+				// The effective copy is first, followed by the rest from highest to lowest version, so this prefers the effective copy.
+				let keep_index =
+					linked_index
+					.or_else(|| config.mods.get(&name).and_then(|req| {
+						let mut matching: Vec<_> = installed_mods.iter().enumerate().filter(|&(_, installed_mod)| req.0.matches(&installed_mod.info.version.0)).collect();
+						matching.sort_by(|&(index1, mod1), &(index2, mod2)| mod1.info.version.cmp(&mod2.info.version).reverse().then_with(|| index1.cmp(&index2)));
+						matching.first().map(|&(index, _)| index)
+					}))
+					.unwrap_or(0);
+
+				kept.push(installed_mods.remove(keep_index));
+				to_move.extend(installed_mods);
+			}
+
+			if to_move.is_empty() {
+				println!("Nothing to do.");
+				return Box::new(future::ok(()));
+			}
+
+			println!("The following copies of mods will be kept:");
+			for installed_mod in &kept {
+				println!("    {} {} ({})", installed_mod.info.name, installed_mod.info.version, installed_mod.path.display());
+			}
+
+			println!();
+			println!("The following copies of mods will be moved to the cache:");
+			for installed_mod in &to_move {
+				println!("    {} {} ({})", installed_mod.info.name, installed_mod.info.version, installed_mod.path.display());
+			}
+
+			println!();
+
+			if global_options.dry_run || !::util::prompt_continue(global_options.prompt_override)? {
+				return Box::new(future::ok(()));
+			}
+
+			::std::fs::create_dir_all(&cache_directory).chain_err(|| format!("Could not create cache directory {}", cache_directory.display()))?;
+
+			for installed_mod in &to_move {
+				println!("    Moving {} {} ...", installed_mod.info.name, installed_mod.info.version);
+				move_to_cache(installed_mod, &cache_directory)?;
+				println!("    Moving {} {} ... done", installed_mod.info.name, installed_mod.info.version);
+			}
+
+			// Versions pinned in mod-list.json must point to the copies that were kept.
+			local_api.update_mod_list(
+				kept.iter().map(|installed_mod| (&installed_mod.info.name, &installed_mod.info.version)),
+				::std::iter::empty(),
+				global_options.enable_new_mods)
+			.chain_err(|| "Could not update mod-list.json")?;
+		};
+
+		Box::new(result.into_future())
+	}
+}
+
+/// Moves the given installed mod into the cache directory.
+///
+/// Zipped mods are moved next to the downloaded releases under the name that releases are downloaded as, so they can be installed again
+/// without downloading them. If the cache already has a different file for the same release, the cached file is kept since it was
+/// verified against the mods portal when it was downloaded, and the installed mod is moved into the `dedupe` subdirectory instead.
+/// Unpacked mods are moved into the `unpacked` subdirectory, replacing a copy of the same release that is already there.
+/// Symbolic links are removed, since whatever they point to is still where it was.
+fn move_to_cache(installed_mod: &::factorio_mods_local::InstalledMod, cache_directory: &::std::path::Path) -> ::Result<()> {
+	let path = &installed_mod.path;
+
+	if let ::link::LinkState::Symlink(_) = ::link::LinkState::of(path)? {
+		return ::link::remove(path);
+	}
+
+	let file_name = format!("{}_{}", installed_mod.info.name, installed_mod.info.version);
+
+	let target = match installed_mod.mod_type {
+		::factorio_mods_local::InstalledModType::Zipped => {
+			let target = cache_directory.join(format!("{}.zip", file_name));
+
+			if ::std::fs::symlink_metadata(&target).is_ok() {
+				if files_equal(path, &target)? {
+					return ::link::remove(path);
+				}
+
+				let dedupe_directory = ::cache::dedupe_directory(cache_directory);
+
+				let mut target = dedupe_directory.join(format!("{}.zip", file_name));
+				let mut i = 1;
+				while ::std::fs::symlink_metadata(&target).is_ok() {
+					i += 1;
+					target = dedupe_directory.join(format!("{} ({}).zip", file_name, i));
+				}
+
+				println!(
+					"    {} {} is different from the cached release {}, so it will be moved to {} instead",
+					installed_mod.info.name, installed_mod.info.version, cache_directory.join(format!("{}.zip", file_name)).display(), target.display());
+
+				target
+			}
+			else {
+				target
+			}
+		},
+
+		::factorio_mods_local::InstalledModType::Unpacked => {
+			let target = ::cache::unpacked_directory(cache_directory).join(&file_name);
+
+			if ::std::fs::symlink_metadata(&target).is_ok() {
+				::link::remove(&target)?;
+			}
+
+			target
+		},
+	};
+
+	let target_directory = target.parent().unwrap();
+	::std::fs::create_dir_all(target_directory).chain_err(|| format!("Could not create directory {}", target_directory.display()))?;
+
+	move_path(path, &target)
+}
+
+/// Moves a file or directory, copying it if it can't be renamed because the source and target are on different filesystems.
+fn move_path(source: &::std::path::Path, target: &::std::path::Path) -> ::Result<()> {
+	match ::std::fs::rename(source, target) {
+		Ok(()) => Ok(()),
+
+		Err(ref err) if is_cross_device(err) => {
+			copy_recursive(source, target)?;
+			::link::remove(source)
+		},
+
+		Err(err) => Err(err).chain_err(|| format!("Could not move {} to {}", source.display(), target.display())),
+	}
+}
+
+/// Returns whether the given error is from renaming a file to a different filesystem.
+#[cfg(target_os = "linux")]
+fn is_cross_device(err: &::std::io::Error) -> bool {
+	err.raw_os_error() == Some(::libc::EXDEV)
+}
+
+/// Returns whether the given error is from renaming a file to a different filesystem.
+#[cfg(all(unix, not(target_os = "linux")))]
+fn is_cross_device(err: &::std::io::Error) -> bool {
+	// EXDEV
+	err.raw_os_error() == Some(18)
+}
+
+/// Returns whether the given error is from renaming a file to a different volume.
+#[cfg(windows)]
+fn is_cross_device(err: &::std::io::Error) -> bool {
+	// ERROR_NOT_SAME_DEVICE
+	err.raw_os_error() == Some(17)
+}
+
+fn copy_recursive(source: &::std::path::Path, target: &::std::path::Path) -> ::Result<()> {
+	if source.is_dir() {
+		::std::fs::create_dir(target).chain_err(|| format!("Could not create directory {}", target.display()))?;
+
+		for directory_entry in ::std::fs::read_dir(source).chain_err(|| format!("Could not read directory {}", source.display()))? {
+			let directory_entry = directory_entry.chain_err(|| format!("Could not read directory {}", source.display()))?;
+			copy_recursive(&directory_entry.path(), &target.join(directory_entry.file_name()))?;
+		}
+	}
+	else {
+		let _ = ::std::fs::copy(source, target).chain_err(|| format!("Could not copy file {} to {}", source.display(), target.display()))?;
+	}
+
+	Ok(())
+}
+
+/// Returns whether the two files have the same contents.
+///
+/// The lengths are compared first, and the contents are only hashed if those match.
+fn files_equal(path1: &::std::path::Path, path2: &::std::path::Path) -> ::Result<bool> {
+	let len1 = ::std::fs::metadata(path1).chain_err(|| format!("Could not read metadata of {}", path1.display()))?.len();
+	let len2 = ::std::fs::metadata(path2).chain_err(|| format!("Could not read metadata of {}", path2.display()))?.len();
+	if len1 != len2 {
+		return Ok(false);
+	}
+
+	Ok(::download::sha1(path1)? == ::download::sha1(path2)?)
+}
@@ -0,0 +1,175 @@
+use ::futures::{ Future, IntoFuture };
+
+use ::ResultExt;
+
+pub struct SubCommand;
+
+impl ::util::SubCommand for SubCommand {
+	fn build_subcommand<'a>(&self, subcommand: ::clap::App<'a, 'a>) -> ::clap::App<'a, 'a> {
+		clap_app!(@app (subcommand)
+			(about: "Develop mods against the mods directory.")
+			(@setting SubcommandRequiredElseHelp)
+			(@subcommand link =>
+				(about: "Link the working copy of a mod into the mods directory.\n\n\
+				         The link is named after the name and version in the mod's info.json. Linked mods are never removed or replaced \
+				         by install, update and remove, but their dependencies are still installed from the mods portal.")
+				(@arg path: +required "path of the directory that contains the mod's info.json"))
+			(@subcommand unlink =>
+				(about: "Remove the link to the working copy of a mod from the mods directory.")
+				(@arg name: +required "name of the mod")))
+	}
+
+	fn run<'a>(
+		&'a self,
+		matches: &'a ::clap::ArgMatches<'a>,
+		local_api: ::Result<&'a ::factorio_mods_local::API>,
+		_: ::Result<&'a ::factorio_mods_web::API>,
+		global_options: ::util::GlobalOptions<'a>,
+	) -> Box<Future<Item = (), Error = ::Error> + 'a> {
+		#[cfg_attr(feature = "cargo-clippy", allow(unit_arg))]
+		let result: ::Result<_> = do catch {
+			let local_api = local_api?;
+
+			match matches.subcommand() {
+				("link", Some(matches)) =>
+					link(::std::path::Path::new(matches.value_of("path").unwrap()), local_api, global_options)?,
+
+				("unlink", Some(matches)) =>
+					unlink(&::factorio_mods_common::ModName(matches.value_of("name").unwrap().to_string()), local_api, global_options)?,
+
+				_ => unreachable!(),
+			}
+		};
+
+		Box::new(result.into_future())
+	}
+}
+
+fn link(path: &::std::path::Path, local_api: &::factorio_mods_local::API, global_options: ::util::GlobalOptions) -> ::Result<()> {
+	let mut config = ::config::Config::load(local_api, global_options.wait_for_lock)?;
+
+	let path = path.canonicalize().chain_err(|| format!("Could not canonicalize {}", path.display()))?;
+	ensure!(path.is_dir(), "{} is not a directory", path.display());
+
+	let working_copy =
+		::factorio_mods_local::InstalledMod::parse(path.clone())
+		.chain_err(|| format!("Could not parse the mod at {}", path.display()))?;
+	let name = &working_copy.info.name;
+	let version = &working_copy.info.version;
+
+	let link_path = local_api.mods_directory().join(format!("{}_{}", name, version));
+
+	// Linking a mod again, such as after the version in its info.json has changed, replaces its previous link.
+	let previous_links = match config.dev_links.get(name) {
+		Some(previous_path) => links_to(local_api, previous_path)?,
+		None => vec![],
+	};
+
+	if config.dev_links.get(name) == Some(&path) && previous_links.contains(&link_path) {
+		println!("{} {} is already linked from {}", name, version, path.display());
+		return Ok(());
+	}
+
+	ensure!(
+		::std::fs::symlink_metadata(&link_path).is_err() || previous_links.contains(&link_path),
+		"{} already exists. Remove it before linking the working copy of {}.",
+		link_path.display(), name);
+
+	println!("Linking {} {} from {} to {} ...", name, version, path.display(), link_path.display());
+
+	if global_options.dry_run {
+		return Ok(());
+	}
+
+	for previous_link in &previous_links {
+		::link::remove(previous_link)?;
+	}
+
+	::link::symlink_directory(&path, &link_path)?;
+
+	local_api.update_mod_list(::std::iter::once((name, version)), ::std::iter::empty(), global_options.enable_new_mods)
+	.chain_err(|| "Could not update mod-list.json")?;
+
+	config.dev_links.insert(name.clone(), path.clone());
+	config.save()?;
+
+	println!("Linking {} {} from {} to {} ... done", name, version, path.display(), link_path.display());
+
+	let other_versions = installed_versions(local_api, name)?.into_iter().filter(|installed_mod| installed_mod.path != link_path).count();
+	if other_versions > 0 {
+		println!("Other installed versions of {} will be removed by the next install, update or remove.", name);
+	}
+
+	Ok(())
+}
+
+fn unlink(name: &::factorio_mods_common::ModName, local_api: &::factorio_mods_local::API, global_options: ::util::GlobalOptions) -> ::Result<()> {
+	let mut config = ::config::Config::load(local_api, global_options.wait_for_lock)?;
+
+	let path = config.dev_links.get(name).cloned().ok_or_else(|| format!("{} is not linked with `fac dev link`", name))?;
+
+	println!("Unlinking {} from {} ...", name, path.display());
+
+	if global_options.dry_run {
+		return Ok(());
+	}
+
+	for link in links_to(local_api, &path)? {
+		::link::remove(&link)?;
+	}
+
+	// Other installed versions of the mod keep its entry, so that whether it's enabled is remembered.
+	if installed_versions(local_api, name)?.is_empty() {
+		local_api.update_mod_list(::std::iter::empty(), ::std::iter::once(name), global_options.enable_new_mods)
+		.chain_err(|| "Could not update mod-list.json")?;
+	}
+
+	config.dev_links.remove(name);
+	config.save()?;
+
+	println!("Unlinking {} from {} ... done", name, path.display());
+
+	if config.mods.contains_key(name) {
+		println!("Run `fac update` to install {} from the mods portal.", name);
+	}
+
+	Ok(())
+}
+
+/// Returns the installed copies of the given mod.
+fn installed_versions(local_api: &::factorio_mods_local::API, name: &::factorio_mods_common::ModName) -> ::Result<Vec<::factorio_mods_local::InstalledMod>> {
+	let mut result = vec![];
+
+	for installed_mod in local_api.installed_mods().chain_err(|| "Could not enumerate installed mods")? {
+		let installed_mod = installed_mod.chain_err(|| "Could not process an installed mod")?;
+		if &installed_mod.info.name == name {
+			result.push(installed_mod);
+		}
+	}
+
+	Ok(result)
+}
+
+/// Returns the paths of the entries in the mods directory that are symbolic links to the given working copy.
+///
+/// The entries are not parsed as mods, so that links are found even if the working copy has been moved or deleted.
+fn links_to(local_api: &::factorio_mods_local::API, working_copy: &::std::path::Path) -> ::Result<Vec<::std::path::PathBuf>> {
+	let mods_directory = local_api.mods_directory();
+
+	let mut result = vec![];
+
+	for directory_entry in ::std::fs::read_dir(mods_directory).chain_err(|| format!("Could not read directory {}", mods_directory.display()))? {
+		let directory_entry = directory_entry.chain_err(|| format!("Could not read directory {}", mods_directory.display()))?;
+		let path = directory_entry.path();
+
+		if let ::link::LinkState::Symlink(target) = ::link::LinkState::of(&path)? {
+			// Relative targets are relative to the directory that contains the link.
+			let target = mods_directory.join(target);
+			if target == working_copy || target.canonicalize().map_or(false, |target| target == working_copy) {
+				result.push(path);
+			}
+		}
+	}
+
+	Ok(result)
+}
@@ -0,0 +1,542 @@
+//! Downloads mod releases to the cache directory, with a limit on the number of concurrent downloads.
+//!
+//! Progress is displayed per file and overall when stdout is a terminal, and as plain log lines otherwise.
+
+use ::futures::{ Async, future, Future, Poll, Stream };
+use ::ResultExt;
+
+/// Downloads mod releases to the cache directory.
+pub struct DownloadManager<'a> {
+	web_api: &'a ::factorio_mods_web::API,
+	user_credentials: ::factorio_mods_common::UserCredentials,
+	cache_directory: ::std::path::PathBuf,
+	cache_directory_canonicalized: ::std::path::PathBuf,
+	state: ::std::rc::Rc<::std::cell::RefCell<State>>,
+}
+
+/// State shared by all the downloads of a `DownloadManager`
+struct State {
+	/// The number of downloads that can still be started before the limit is reached.
+	available_slots: usize,
+
+	/// Tasks waiting for a download slot to become available, each with the ID of the `Acquire` that is waiting.
+	waiting: ::std::collections::VecDeque<(usize, ::futures::task::Task)>,
+
+	/// The ID to give to the next `Acquire` that has to wait.
+	next_waiter_id: usize,
+
+	progress: Progress,
+}
+
+impl<'a> DownloadManager<'a> {
+	/// Creates a download manager that downloads at most `max_concurrent_downloads` files at the same time.
+	pub fn new(
+		web_api: &'a ::factorio_mods_web::API,
+		user_credentials: ::factorio_mods_common::UserCredentials,
+		cache_directory: ::std::path::PathBuf,
+		cache_directory_canonicalized: ::std::path::PathBuf,
+		max_concurrent_downloads: usize,
+	) -> Self {
+		DownloadManager {
+			web_api,
+			user_credentials,
+			cache_directory,
+			cache_directory_canonicalized,
+			state: ::std::rc::Rc::new(::std::cell::RefCell::new(State {
+				available_slots: max_concurrent_downloads,
+				waiting: Default::default(),
+				next_waiter_id: 0,
+				progress: Progress::new(::term_size::dimensions_stdout().is_some()),
+			})),
+		}
+	}
+
+	/// Downloads the given releases to the cache directory.
+	pub fn download_all(
+		&self,
+		releases: &[(::factorio_mods_common::ModName, ::factorio_mods_web::ModRelease)],
+	) -> impl Future<Item = (), Error = ::Error> + 'static {
+		let futures: Vec<_> = releases.iter().map(|(mod_name, release)| self.download(mod_name, release)).collect();
+		future::join_all(futures).map(|_| ())
+	}
+
+	/// Downloads the given release to the cache directory, unless it has already been downloaded. Resolves to the path of the cached file.
+	///
+	/// The release is downloaded to a `.new` file first, which is renamed once the download has completed and its SHA-1 hash has been verified.
+	/// If a `.new` file already exists from an earlier interrupted download, the download is resumed from where it left off.
+	pub fn download(
+		&self,
+		mod_name: &::factorio_mods_common::ModName,
+		release: &::factorio_mods_web::ModRelease,
+	) -> Box<Future<Item = ::std::path::PathBuf, Error = ::Error>> {
+		let filename = self.cache_directory.join(&release.filename.0);
+		if filename.exists() {
+			return Box::new(future::ok(filename));
+		}
+
+		let download_filename = match download_filename(&filename, &self.cache_directory_canonicalized) {
+			Ok(download_filename) => download_filename,
+			Err(err) => return Box::new(future::err(err)),
+		};
+
+		let resume_from = match ::std::fs::metadata(&download_filename) {
+			Ok(ref metadata) if metadata.is_file() && metadata.len() > 0 => Some(metadata.len()),
+			_ => None,
+		};
+
+		let description = format!("{} {}", mod_name, release.version);
+
+		// The requests aren't sent until these futures are polled, which only happens once a download slot is available.
+		// `restart` is only used if the partial file can't be resumed.
+		let download = self.web_api.download(release, &self.user_credentials, resume_from);
+		let restart = self.web_api.download(release, &self.user_credentials, None);
+
+		let expected_sha1 = release.sha1.0.to_lowercase();
+
+		let state = self.state.clone();
+		let id = state.borrow_mut().progress.add(description.clone());
+		let acquire = Acquire { state: state.clone(), waiter_id: None };
+
+		let progress_state = state.clone();
+
+		let future = ::async_block! {
+			let displayable_filename = filename.display().to_string();
+			let download_displayable_filename = download_filename.display().to_string();
+
+			let _slot = ::await!(acquire)?;
+
+			let download = match ::await!(download) {
+				// The partial file is at least as long as the whole file, so it must be corrupt. Start over.
+				Err(::factorio_mods_web::Error(::factorio_mods_web::ErrorKind::StatusCode(_, ::factorio_mods_web::reqwest::StatusCode::RangeNotSatisfiable), _)) =>
+					::await!(restart),
+
+				result => result,
+			};
+			let download = download.chain_err(|| format!("Could not download release {}", description))?;
+
+			let resumed_from = download.resumed_from.unwrap_or(0);
+			state.borrow_mut().progress.start(id, resumed_from, download.content_length.map(|content_length| resumed_from + content_length));
+
+			// If the server ignored the request to resume the download, it's sending the whole file, so overwrite the partial file.
+			let mut download_file = ::std::fs::OpenOptions::new();
+			let download_file =
+				if download.resumed_from.is_some() {
+					download_file.append(true)
+				}
+				else {
+					download_file.create(true).truncate(true).write(true)
+				};
+			let download_file = download_file.open(&download_filename).chain_err(|| format!("Could not open {} for writing", download_displayable_filename))?;
+			let mut download_file = ::std::io::BufWriter::new(download_file);
+
+			// Errors partway through the file aren't retried, but the partial file is kept so that the next attempt resumes from there.
+			let chunks = {
+				let description = description.clone();
+				download.then(move |chunk| chunk.chain_err(|| format!("Could not download release {}. Run the command again to resume the download.", description)))
+			};
+
+			#[async] for chunk in chunks {
+				::std::io::Write::write_all(&mut download_file, &chunk)
+				.chain_err(|| format!("Could not write to file {}", download_displayable_filename))?;
+
+				state.borrow_mut().progress.advance(id, chunk.len() as u64);
+			}
+
+			::std::io::Write::flush(&mut download_file)
+			.chain_err(|| format!("Could not write to file {}", download_displayable_filename))?;
+			drop(download_file);
+
+			let actual_sha1 = sha1(&download_filename)?;
+			if actual_sha1 != expected_sha1 {
+				// Don't try to resume from a corrupt file next time.
+				let _ = ::std::fs::remove_file(&download_filename);
+				bail!(
+					"Release {} is corrupt: expected SHA-1 hash {} but the downloaded file has {}. Run the command again to download it from the beginning.",
+					description, expected_sha1, actual_sha1);
+			}
+
+			::std::fs::rename(&download_filename, &filename)
+			.chain_err(|| format!("Could not rename {} to {}", download_displayable_filename, displayable_filename))?;
+
+			Ok(filename)
+		};
+
+		Box::new(future.then(move |result| {
+			match result {
+				Ok(_) => progress_state.borrow_mut().progress.finish(id),
+				Err(_) => progress_state.borrow_mut().progress.fail(id),
+			}
+
+			result
+		}))
+	}
+}
+
+/// Returns the path of the `.new` file that the given cached file is downloaded to, after ensuring that it is inside the cache directory.
+fn download_filename(filename: &::std::path::Path, cache_directory_canonicalized: &::std::path::Path) -> ::Result<::std::path::PathBuf> {
+	let displayable_filename = filename.display();
+
+	let mut download_filename: ::std::ffi::OsString =
+		filename.file_name()
+		.ok_or_else(|| format!("Could not parse filename {}", displayable_filename))?
+		.into();
+
+	download_filename.push(".new");
+	let download_filename = filename.with_file_name(download_filename);
+	let download_displayable_filename = download_filename.display();
+
+	let parent = download_filename.parent().ok_or_else(|| format!("Filename {} is malformed", download_displayable_filename))?;
+	let parent_canonicalized = parent.canonicalize().chain_err(|| format!("Filename {} is malformed", download_displayable_filename))?;
+	ensure!(parent_canonicalized == cache_directory_canonicalized, "Filename {} is malformed", download_displayable_filename);
+
+	Ok(download_filename)
+}
+
+/// Computes the SHA-1 hash of the given file, as a lowercase hex string.
+pub fn sha1(path: &::std::path::Path) -> ::Result<String> {
+	let mut file = ::std::fs::File::open(path).chain_err(|| format!("Could not open {} for reading", path.display()))?;
+
+	let mut hasher = ::sha1::Sha1::new();
+	let mut buf = vec![0; 64 * 1024];
+
+	loop {
+		let read = ::std::io::Read::read(&mut file, &mut buf).chain_err(|| format!("Could not read from file {}", path.display()))?;
+		if read == 0 {
+			break;
+		}
+
+		hasher.update(&buf[..read]);
+	}
+
+	Ok(hasher.digest().to_string())
+}
+
+/// Resolves to a `Slot` once fewer than the maximum number of downloads are in progress.
+struct Acquire {
+	state: ::std::rc::Rc<::std::cell::RefCell<State>>,
+
+	/// The ID of this future's entry in `State::waiting`, if it has one.
+	waiter_id: Option<usize>,
+}
+
+impl Future for Acquire {
+	type Item = Slot;
+	type Error = ::Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		let mut state = self.state.borrow_mut();
+
+		if state.available_slots > 0 {
+			state.available_slots -= 1;
+
+			if let Some(waiter_id) = self.waiter_id.take() {
+				state.waiting.retain(|&(id, _)| id != waiter_id);
+			}
+
+			Ok(Async::Ready(Slot { state: self.state.clone() }))
+		}
+		else {
+			// All downloads can be polled by the same task, so this future is polled whenever any of them makes progress.
+			// It must only have one entry in the queue, otherwise the queue would grow with every poll.
+			let task = ::futures::task::current();
+
+			let waiter = self.waiter_id.and_then(|waiter_id| state.waiting.iter_mut().find(|&&mut (id, _)| id == waiter_id));
+			match waiter {
+				Some(waiter) => waiter.1 = task,
+
+				// Not queued yet, or was notified but another download took the slot first.
+				None => {
+					let waiter_id = state.next_waiter_id;
+					state.next_waiter_id += 1;
+					state.waiting.push_back((waiter_id, task));
+					self.waiter_id = Some(waiter_id);
+				},
+			}
+
+			Ok(Async::NotReady)
+		}
+	}
+}
+
+impl Drop for Acquire {
+	fn drop(&mut self) {
+		if let Some(waiter_id) = self.waiter_id {
+			let mut state = self.state.borrow_mut();
+
+			let original_len = state.waiting.len();
+			state.waiting.retain(|&(id, _)| id != waiter_id);
+
+			// If this future had already been notified of a free slot, pass the notification on to the next waiting download.
+			if state.waiting.len() == original_len && state.available_slots > 0 {
+				if let Some((_, task)) = state.waiting.pop_front() {
+					task.notify();
+				}
+			}
+		}
+	}
+}
+
+/// A download slot. The slot is released when this is dropped.
+struct Slot {
+	state: ::std::rc::Rc<::std::cell::RefCell<State>>,
+}
+
+impl Drop for Slot {
+	fn drop(&mut self) {
+		let mut state = self.state.borrow_mut();
+
+		state.available_slots += 1;
+
+		if let Some((_, task)) = state.waiting.pop_front() {
+			task.notify();
+		}
+	}
+}
+
+/// Displays the progress of downloads.
+struct Progress {
+	/// Whether stdout is a terminal, in which case progress bars are drawn. Otherwise progress is logged as plain lines.
+	is_terminal: bool,
+
+	/// All the downloads that have been added, including those that have finished.
+	files: Vec<FileProgress>,
+
+	/// The number of lines of progress bars currently on the screen, above the cursor.
+	lines_drawn: usize,
+
+	/// The last time the progress bars were drawn.
+	last_drawn: Option<::std::time::Instant>,
+
+	/// The time the first download was started.
+	started: Option<::std::time::Instant>,
+}
+
+/// The progress of a single download.
+struct FileProgress {
+	description: String,
+	state: FileState,
+
+	/// The number of bytes of the file that have been downloaded, including those downloaded by an earlier interrupted download.
+	downloaded: u64,
+
+	/// The number of bytes that had been downloaded by an earlier interrupted download.
+	resumed_from: u64,
+
+	total: Option<u64>,
+	started: Option<::std::time::Instant>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum FileState {
+	Waiting,
+	Downloading,
+	Finished,
+	Failed,
+}
+
+/// The width of a progress bar, in characters.
+const PROGRESS_BAR_WIDTH: u64 = 20;
+
+/// The minimum time between redraws of the progress bars, in milliseconds.
+const REDRAW_INTERVAL_MILLIS: u64 = 100;
+
+impl Progress {
+	fn new(is_terminal: bool) -> Self {
+		Progress {
+			is_terminal,
+			files: vec![],
+			lines_drawn: 0,
+			last_drawn: None,
+			started: None,
+		}
+	}
+
+	/// Adds a download that is waiting to be started, and returns its ID.
+	fn add(&mut self, description: String) -> usize {
+		self.files.push(FileProgress {
+			description,
+			state: FileState::Waiting,
+			downloaded: 0,
+			resumed_from: 0,
+			total: None,
+			started: None,
+		});
+
+		self.files.len() - 1
+	}
+
+	/// Marks the given download as started. `resumed_from` is the number of bytes downloaded by an earlier interrupted download, and `total`
+	/// is the size of the file, if known.
+	fn start(&mut self, id: usize, resumed_from: u64, total: Option<u64>) {
+		let now = ::std::time::Instant::now();
+
+		if self.started.is_none() {
+			self.started = Some(now);
+		}
+
+		{
+			let file = &mut self.files[id];
+			file.state = FileState::Downloading;
+			file.downloaded = resumed_from;
+			file.resumed_from = resumed_from;
+			file.total = total;
+			file.started = Some(now);
+		}
+
+		if self.is_terminal {
+			self.draw(true);
+		}
+		else {
+			let file = &self.files[id];
+			let size = file.total.map_or_else(String::new, |total| format!(" ({})", ::util::format_bytes(total)));
+			if file.resumed_from > 0 {
+				println!("    Downloading {}{} ... resuming from {}", file.description, size, ::util::format_bytes(file.resumed_from));
+			}
+			else {
+				println!("    Downloading {}{} ...", file.description, size);
+			}
+		}
+	}
+
+	/// Records that the given number of bytes of the given download have been received.
+	fn advance(&mut self, id: usize, bytes: u64) {
+		self.files[id].downloaded += bytes;
+
+		if self.is_terminal {
+			self.draw(false);
+		}
+	}
+
+	/// Marks the given download as finished.
+	fn finish(&mut self, id: usize) {
+		self.files[id].state = FileState::Finished;
+
+		if self.is_terminal {
+			self.clear();
+		}
+
+		{
+			let file = &self.files[id];
+			let elapsed = file.started.map_or(Default::default(), |started| started.elapsed());
+			println!("    Downloading {} ... done ({} in {})", file.description, ::util::format_bytes(file.downloaded - file.resumed_from), format_duration(elapsed));
+		}
+
+		if self.is_terminal {
+			self.draw(true);
+		}
+	}
+
+	/// Marks the given download as failed. The error itself is reported by whoever awaits the download.
+	fn fail(&mut self, id: usize) {
+		self.files[id].state = FileState::Failed;
+
+		if self.is_terminal {
+			self.clear();
+		}
+
+		println!("    Downloading {} ... failed", self.files[id].description);
+
+		if self.is_terminal {
+			self.draw(true);
+		}
+	}
+
+	/// Erases the progress bars from the screen.
+	fn clear(&mut self) {
+		for _ in 0..self.lines_drawn {
+			// Move the cursor up one line and erase that line.
+			print!("\x1b[1A\x1b[2K");
+		}
+
+		self.lines_drawn = 0;
+	}
+
+	/// Redraws the progress bars. Unless `force` is set, this does nothing if they were drawn very recently.
+	fn draw(&mut self, force: bool) {
+		let now = ::std::time::Instant::now();
+
+		if !force {
+			if let Some(last_drawn) = self.last_drawn {
+				if now.duration_since(last_drawn) < ::std::time::Duration::from_millis(REDRAW_INTERVAL_MILLIS) {
+					return;
+				}
+			}
+		}
+
+		self.clear();
+
+		let mut lines = vec![];
+
+		for file in self.files.iter().filter(|file| file.state == FileState::Downloading) {
+			let elapsed = file.started.map_or(Default::default(), |started| now.duration_since(started));
+			lines.push(format!("    {:<40} {}", file.description, format_progress(file.downloaded, file.resumed_from, file.total, elapsed)));
+		}
+
+		let num_finished = self.files.iter().filter(|file| file.state == FileState::Finished || file.state == FileState::Failed).count();
+
+		if num_finished < self.files.len() {
+			let downloaded = self.files.iter().map(|file| file.downloaded).sum();
+			let resumed_from = self.files.iter().map(|file| file.resumed_from).sum();
+
+			// The overall total is only known once every file has been started and the server has reported its size.
+			let total = self.files.iter().map(|file| file.total).fold(Some(0), |sum, total| match (sum, total) {
+				(Some(sum), Some(total)) => Some(sum + total),
+				_ => None,
+			});
+
+			let elapsed = self.started.map_or(Default::default(), |started| now.duration_since(started));
+
+			lines.push(format!(
+				"    {:<40} {}",
+				format!("Total ({} of {} files)", num_finished, self.files.len()),
+				format_progress(downloaded, resumed_from, total, elapsed)));
+		}
+
+		for line in &lines {
+			println!("{}", line);
+		}
+
+		let _ = ::std::io::Write::flush(&mut ::std::io::stdout());
+
+		self.lines_drawn = lines.len();
+		self.last_drawn = Some(now);
+	}
+}
+
+/// Formats a progress bar with the number of bytes downloaded, the download rate and the estimated time remaining.
+///
+/// Bytes before `resumed_from` were downloaded by an earlier interrupted download, so they don't count towards the download rate.
+fn format_progress(downloaded: u64, resumed_from: u64, total: Option<u64>, elapsed: ::std::time::Duration) -> String {
+	let elapsed_millis = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_nanos() / 1_000_000);
+	let rate = if elapsed_millis == 0 { 0 } else { (downloaded - resumed_from) * 1000 / elapsed_millis };
+
+	match total {
+		Some(total) if total > 0 => {
+			let filled = downloaded.min(total) * PROGRESS_BAR_WIDTH / total;
+			let bar: String = (0..PROGRESS_BAR_WIDTH).map(|i| if i < filled { '#' } else { ' ' }).collect();
+
+			let eta =
+				if rate == 0 {
+					"--:--".to_string()
+				}
+				else {
+					format_duration(::std::time::Duration::from_secs(total.saturating_sub(downloaded) / rate))
+				};
+
+			format!(
+				"[{}] {} / {} {}/s ETA {}",
+				bar,
+				::util::format_bytes(downloaded), ::util::format_bytes(total),
+				::util::format_bytes(rate),
+				eta)
+		},
+
+		_ => format!("{} {}/s", ::util::format_bytes(downloaded), ::util::format_bytes(rate)),
+	}
+}
+
+/// Formats the given duration as minutes and seconds.
+fn format_duration(duration: ::std::time::Duration) -> String {
+	let secs = duration.as_secs();
+	format!("{}:{:02}", secs / 60, secs % 60)
+}
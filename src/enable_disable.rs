@@ -1,4 +1,4 @@
-use ::futures::{ future, Future, IntoFuture };
+use ::futures::Future;
 
 use ::ResultExt;
 
@@ -6,19 +6,19 @@ pub struct EnableSubCommand;
 
 impl ::util::SubCommand for EnableSubCommand {
 	fn build_subcommand<'a>(&self, subcommand: ::clap::App<'a, 'a>) -> ::clap::App<'a, 'a> {
-		clap_app!(@app (subcommand)
-			(about: "Enable mods.")
-			(@arg mods: ... +required index(1) "mods to enable"))
+		add_selector_args(clap_app!(@app (subcommand)
+			(about: "Enable mods, and the mods they depend on.")
+			(@arg install_missing: long("install-missing") "download and install required dependencies that aren't installed, then enable them too")))
 	}
 
 	fn run<'a>(
 		&'a self,
 		matches: &'a ::clap::ArgMatches<'a>,
 		local_api: ::Result<&'a ::factorio_mods_local::API>,
-		_: ::Result<&'a ::factorio_mods_web::API>,
-		prompt_override: Option<bool>,
+		web_api: ::Result<&'a ::factorio_mods_web::API>,
+		global_options: ::util::GlobalOptions<'a>,
 	) -> Box<Future<Item = (), Error = ::Error> + 'a> {
-		enable_disable(matches, local_api, prompt_override, true)
+		enable_disable(matches, local_api, web_api, global_options, true)
 	}
 }
 
@@ -26,102 +26,258 @@ pub struct DisableSubCommand;
 
 impl ::util::SubCommand for DisableSubCommand {
 	fn build_subcommand<'a>(&self, subcommand: ::clap::App<'a, 'a>) -> ::clap::App<'a, 'a> {
-		clap_app!(@app (subcommand)
-			(about: "Disable mods.")
-			(@arg mods: ... +required index(1) "mods to disable"))
+		add_selector_args(clap_app!(@app (subcommand)
+			(about: "Disable mods, and the mods that depend on them.")))
 	}
 
 	fn run<'a>(
 		&'a self,
 		matches: &'a ::clap::ArgMatches<'a>,
 		local_api: ::Result<&'a ::factorio_mods_local::API>,
-		_: ::Result<&'a ::factorio_mods_web::API>,
-		prompt_override: Option<bool>,
+		web_api: ::Result<&'a ::factorio_mods_web::API>,
+		global_options: ::util::GlobalOptions<'a>,
 	) -> Box<Future<Item = (), Error = ::Error> + 'a> {
-		enable_disable(matches, local_api, prompt_override, false)
+		enable_disable(matches, local_api, web_api, global_options, false)
 	}
 }
 
+/// Adds the arguments that select which mods to enable or disable.
+fn add_selector_args<'a>(subcommand: ::clap::App<'a, 'a>) -> ::clap::App<'a, 'a> {
+	clap_app!(@app (subcommand)
+		(@arg mods: ... index(1) required_unless_one(&["all", "author", "from_file"]) "names of mods, or glob patterns such as bob*")
+		(@arg all: --all "all installed mods")
+		(@arg author: --author +takes_value "all installed mods by this author")
+		(@arg from_file: long("from-file") +takes_value "a file with one mod name or glob pattern per line. Empty lines and lines starting with # are ignored.")
+		(@arg except: --except +takes_value +use_delimiter "comma-separated names or glob patterns of mods to leave unchanged. Mods that can't be changed without also changing these are left unchanged too."))
+}
+
 fn enable_disable<'a>(
 	matches: &'a ::clap::ArgMatches<'a>,
 	local_api: ::Result<&'a ::factorio_mods_local::API>,
-	prompt_override: Option<bool>,
+	web_api: ::Result<&'a ::factorio_mods_web::API>,
+	global_options: ::util::GlobalOptions<'a>,
 	enable: bool,
 ) -> Box<Future<Item = (), Error = ::Error> + 'a> {
-	let result: ::Result<_> = do catch {
-		let mods = matches.values_of("mods").unwrap();
-
+	Box::new(::async_block! {
 		let local_api = local_api?;
 
-		let all_installed_mods: ::Result<::multimap::MultiMap<_, _>> =
-			local_api.installed_mods().chain_err(|| "Could not enumerate installed mods")?
-			.map(|mod_| mod_.map(|mod_| (mod_.info.name.clone(), mod_)).chain_err(|| "Could not process an installed mod"))
-			.collect();
-		let all_installed_mods = all_installed_mods.chain_err(|| "Could not enumerate installed mods")?;
+		// Held until the mods have been enabled or disabled, including while missing dependencies are installed.
+		let _lock = ::lock::Lock::acquire(Some(local_api), global_options.wait_for_lock)?;
 
-		for (name, installed_mods) in &all_installed_mods {
-			if installed_mods.len() > 1 {
-				println!("There is more than one version of {} installed. Run `fac update` or remove all but one version manually.", name);
-				return Box::new(future::ok(()));
-			}
-		}
+		let to_change = match select(matches, local_api, enable)? {
+			Selection::Mods(to_change) => {
+				print_to_change(&to_change.iter().map(|installed_mod| &installed_mod.info.name).collect::<Vec<_>>(), enable);
+
+				if global_options.dry_run || !::util::prompt_continue(global_options.prompt_override)? {
+					return Ok(());
+				}
 
-		let mut graph = ::petgraph::Graph::new();
-
-		let name_to_node_index: ::std::collections::HashMap<_, _> =
-			all_installed_mods.into_iter().map(|(name, mut installed_mods)| (name, graph.add_node(installed_mods.remove(0)))).collect();
-
-		let mut edges_to_add = vec![];
-		for node_index in graph.node_indices() {
-			let installed_mod = &graph[node_index];
-			for dep in &installed_mod.info.dependencies {
-				if dep.required && dep.name.0 != "base" {
-					if let Some(&dep_node_index) = name_to_node_index.get(&dep.name) {
-						if enable {
-							edges_to_add.push((node_index, dep_node_index));
-						}
-						else {
-							edges_to_add.push((dep_node_index, node_index));
-						}
+				to_change
+			},
+
+			Selection::Missing(to_change, missing) => {
+				if !matches.is_present("install_missing") {
+					for (dependent, dependency) in missing {
+						println!(
+							"Mod {} is a required dependency of {} but isn't installed. \
+							 Run `fac enable --install-missing` or `fac update` to install missing dependencies.",
+							dependency.name, dependent);
+					}
+					return Ok(());
+				}
+
+				// Only the missing dependencies are installed. The mods that need them stay at their installed versions, and the config is unchanged.
+				let dependent_names: ::std::collections::HashSet<_> = missing.iter().map(|&(ref dependent, _)| dependent).collect();
+				let dependents: Vec<_> = to_change.iter().filter(|installed_mod| dependent_names.contains(&installed_mod.info.name)).cloned().collect();
+				let config = ::config::Config::load_read_only(local_api)?;
+
+				let mut names: Vec<_> = to_change.iter().map(|installed_mod| &installed_mod.info.name).collect();
+				names.extend(missing.iter().map(|&(_, ref dependency)| &dependency.name));
+				names.sort();
+				names.dedup();
+				print_to_change(&names, enable);
+
+				// The plan is confirmed once for both installing the missing dependencies and enabling the mods.
+				let web_api = web_api?;
+				if !::await!(::solve::install_missing_dependencies(local_api, web_api, dependents, config.mods, global_options))? {
+					return Ok(());
+				}
+
+				match select(matches, local_api, enable)? {
+					Selection::Mods(to_change) => to_change,
+					Selection::Missing(_, missing) => bail!(
+						"Mod {} is still not installed after installing missing dependencies",
+						::itertools::join(missing.iter().map(|&(_, ref dependency)| &dependency.name), ", ")),
+					Selection::Nothing => return Ok(()),
+				}
+			},
+
+			Selection::Nothing => return Ok(()),
+		};
+
+		local_api.set_enabled(&to_change, enable).chain_err(|| format!("Could not {} mods", if enable { "enable" } else { "disable" }))?;
+
+		Ok(())
+	})
+}
+
+/// The mods selected by the arguments of `fac enable` or `fac disable`, along with the mods that have to change with them.
+enum Selection {
+	/// The installed mods to change.
+	Mods(Vec<::factorio_mods_local::InstalledMod>),
+
+	/// The installed mods to change, and required dependencies of them that aren't installed, along with the name of the mod that needs each one.
+	Missing(Vec<::factorio_mods_local::InstalledMod>, Vec<(::factorio_mods_common::ModName, ::factorio_mods_common::Dependency)>),
+
+	/// There is nothing to change. The reason has already been printed.
+	Nothing,
+}
+
+/// Finds the mods selected by the given arguments, and the mods that have to be enabled or disabled with them.
+fn select(
+	matches: &::clap::ArgMatches,
+	local_api: &::factorio_mods_local::API,
+	enable: bool,
+) -> ::Result<Selection> {
+	// When more than one version of a mod is installed, only the one that the game loads matters.
+	let effective_mods = local_api.effective_mods().chain_err(|| "Could not enumerate installed mods")?;
+
+	let mut graph = ::petgraph::Graph::new();
+
+	let name_to_node_index: ::std::collections::HashMap<_, _> =
+		effective_mods.into_iter().map(|(name, versions)| (name, graph.add_node(versions.effective))).collect();
+
+	let mut edges_to_add = vec![];
+	let mut missing_dependencies: ::multimap::MultiMap<_, _> = Default::default();
+	for node_index in graph.node_indices() {
+		let installed_mod = &graph[node_index];
+		for dep in &installed_mod.info.dependencies {
+			if dep.required && dep.name.0 != "base" {
+				if let Some(&dep_node_index) = name_to_node_index.get(&dep.name) {
+					if enable {
+						edges_to_add.push((node_index, dep_node_index));
 					}
 					else {
-						println!("Mod {} is a required dependency of {} but isn't installed. Run `fac update` to install missing dependencies.", dep.name, installed_mod.info.name);
-						return Box::new(future::ok(()));
+						edges_to_add.push((dep_node_index, node_index));
 					}
 				}
+				else {
+					missing_dependencies.insert(node_index, dep.clone());
+				}
 			}
 		}
-		for edge_to_add in edges_to_add {
-			graph.add_edge(edge_to_add.0, edge_to_add.1, ());
+	}
+	for edge_to_add in edges_to_add {
+		graph.add_edge(edge_to_add.0, edge_to_add.1, ());
+	}
+
+	let mut selected = ::std::collections::HashSet::new();
+
+	if matches.is_present("all") {
+		selected.extend(graph.node_indices());
+	}
+
+	if let Some(author) = matches.value_of("author") {
+		let author_lowercase = author.to_lowercase();
+		let by_author: Vec<_> =
+			graph.node_indices()
+			.filter(|&node_index| graph[node_index].info.author.iter().any(|mod_author| mod_author.0.to_lowercase() == author_lowercase))
+			.collect();
+		if by_author.is_empty() {
+			println!("No installed mods by author {}", author);
+			return Ok(Selection::Nothing);
 		}
+		selected.extend(by_author);
+	}
 
-		let mut to_change = ::std::collections::HashSet::new();
+	let mut patterns: Vec<_> = matches.values_of("mods").into_iter().flat_map(|mods| mods).map(String::from).collect();
 
-		for name in mods {
-			if let Some(&node_index) = name_to_node_index.get(&::factorio_mods_common::ModName(name.to_string())) {
-				let bfs = ::petgraph::visit::Bfs::new(&graph, node_index);
-				to_change.extend(::petgraph::visit::Walker::iter(bfs, &graph));
-			}
-			else {
-				println!("No match found for mod {}", name);
-				return Box::new(future::ok(()));
-			}
+	if let Some(path) = matches.value_of("from_file") {
+		let contents = ::std::fs::read_to_string(path).chain_err(|| format!("Could not read {}", path))?;
+		patterns.extend(
+			contents.lines()
+			.map(str::trim)
+			.filter(|line| !line.is_empty() && !line.starts_with('#'))
+			.map(String::from));
+	}
+
+	for pattern in &patterns {
+		let matching = matching_mods(pattern, &name_to_node_index)?;
+		if matching.is_empty() {
+			println!("No match found for mod {}", pattern);
+			return Ok(Selection::Nothing);
 		}
+		selected.extend(matching);
+	}
+
+	let mut excluded = ::std::collections::HashSet::new();
+	for pattern in matches.values_of("except").into_iter().flat_map(|except| except) {
+		excluded.extend(matching_mods(pattern, &name_to_node_index)?);
+	}
 
-		let mut to_change: Vec<_> = to_change.into_iter().map(|node_index| &graph[node_index]).collect();
-		to_change.sort_by(|mod1, mod2| mod1.info.name.cmp(&mod2.info.name));
+	let mut to_change = ::std::collections::HashSet::new();
 
-		println!("The following mods will be {}:", if enable { "enabled" } else { "disabled" });
-		for to_change in &to_change {
-			println!("{}", to_change.info.name);
+	for node_index in selected {
+		let bfs = ::petgraph::visit::Bfs::new(&graph, node_index);
+		let closure: Vec<_> = ::petgraph::visit::Walker::iter(bfs, &graph).collect();
+
+		// Changing this mod would also change an excluded mod, or leave it without a dependency, so leave it alone.
+		if closure.iter().any(|node_index| excluded.contains(node_index)) {
+			continue;
 		}
 
-		println!();
-		if !::util::prompt_continue(prompt_override)? {
-			return Box::new(future::ok(()));
+		to_change.extend(closure);
+	}
+
+	if to_change.is_empty() {
+		println!("Nothing to do.");
+		return Ok(Selection::Nothing);
+	}
+
+	// Disabling a mod doesn't need its dependencies to be installed.
+	let missing: Vec<_> =
+		if enable {
+			to_change.iter()
+			.flat_map(|&node_index| missing_dependencies.get_vec(&node_index).into_iter().flat_map(|dependencies| dependencies).map(move |dependency| (node_index, dependency)))
+			.map(|(node_index, dependency)| (graph[node_index].info.name.clone(), dependency.clone()))
+			.collect()
 		}
+		else {
+			vec![]
+		};
+
+	let mut to_change: Vec<_> = to_change.into_iter().map(|node_index| graph[node_index].clone()).collect();
+	to_change.sort_by(|mod1, mod2| mod1.info.name.cmp(&mod2.info.name));
+
+	if missing.is_empty() {
+		Ok(Selection::Mods(to_change))
+	}
+	else {
+		Ok(Selection::Missing(to_change, missing))
+	}
+}
+
+fn print_to_change(names: &[&::factorio_mods_common::ModName], enable: bool) {
+	println!("The following mods will be {}:", if enable { "enabled" } else { "disabled" });
+	for name in names {
+		println!("{}", name);
+	}
+
+	println!();
+}
+
+/// Returns the nodes of the installed mods whose names match the given glob pattern.
+///
+/// The pattern is only matched against the names of the mods that have already been parsed, so the mods directory isn't read again.
+fn matching_mods(
+	pattern: &str,
+	name_to_node_index: &::std::collections::HashMap<::factorio_mods_common::ModName, ::petgraph::graph::NodeIndex>,
+) -> ::Result<Vec<::petgraph::graph::NodeIndex>> {
+	let matcher = ::globset::Glob::new(pattern).chain_err(|| format!("Could not parse pattern {}", pattern))?.compile_matcher();
 
-		local_api.set_enabled(to_change, enable).chain_err(|| format!("Could not {} mods", if enable { "enable" } else { "disable" }))?;
-	};
-	Box::new(result.into_future())
+	Ok(
+		name_to_node_index.iter()
+		.filter(|&(name, _)| matcher.is_match(&name.0))
+		.map(|(_, &node_index)| node_index)
+		.collect())
 }
@@ -0,0 +1,381 @@
+use ::futures::{ Future, IntoFuture };
+
+use ::ResultExt;
+
+pub struct TreeSubCommand;
+
+impl ::util::SubCommand for TreeSubCommand {
+	fn build_subcommand<'a>(&self, subcommand: ::clap::App<'a, 'a>) -> ::clap::App<'a, 'a> {
+		clap_app!(@app (subcommand)
+			(about: "Show the dependency tree of installed mods.")
+			(@arg mod: index(1) "mod to show the dependency tree of (default: all installed mods)"))
+	}
+
+	fn run<'a>(
+		&'a self,
+		matches: &'a ::clap::ArgMatches<'a>,
+		local_api: ::Result<&'a ::factorio_mods_local::API>,
+		_: ::Result<&'a ::factorio_mods_web::API>,
+		_: ::util::GlobalOptions<'a>,
+	) -> Box<Future<Item = (), Error = ::Error> + 'a> {
+		#[cfg_attr(feature = "cargo-clippy", allow(unit_arg))]
+		let result: ::Result<_> = do catch {
+			let local_api = local_api?;
+
+			let graph = DependencyGraph::new(local_api)?;
+
+			let mut shown = ::std::collections::HashSet::new();
+
+			if let Some(name) = matches.value_of("mod") {
+				let node_index = match graph.name_to_node_index.get(&::factorio_mods_common::ModName(name.to_string())) {
+					Some(&node_index) if graph.graph[node_index].installed_mod().is_some() => node_index,
+					_ => bail!("Mod {} is not installed", name),
+				};
+
+				graph.print_tree(node_index, "", &mut vec![], &mut shown);
+			}
+			else {
+				let mut installed: Vec<_> =
+					graph.graph.node_indices()
+					.filter(|&node_index| graph.graph[node_index].installed_mod().is_some())
+					.collect();
+
+				if installed.is_empty() {
+					println!("No installed mods.");
+				}
+				else {
+					installed.sort_by(|&node_index1, &node_index2| graph.graph[node_index1].name().cmp(graph.graph[node_index2].name()));
+
+					// Roots are the mods that no other installed mod requires. Whatever is left over after printing them is part of a cycle.
+					let (roots, rest): (Vec<_>, Vec<_>) = installed.into_iter().partition(|&node_index|
+						!graph.graph.edges_directed(node_index, ::petgraph::Direction::Incoming).any(|edge| edge.weight().required));
+
+					for node_index in roots.into_iter().chain(rest) {
+						if !shown.contains(&node_index) {
+							graph.print_tree(node_index, "", &mut vec![], &mut shown);
+						}
+					}
+				}
+			}
+		};
+
+		Box::new(result.into_future())
+	}
+}
+
+pub struct GraphSubCommand;
+
+impl ::util::SubCommand for GraphSubCommand {
+	fn build_subcommand<'a>(&self, subcommand: ::clap::App<'a, 'a>) -> ::clap::App<'a, 'a> {
+		clap_app!(@app (subcommand)
+			(about: "Export the dependency graph of installed mods.")
+			(@arg format: --format +takes_value possible_values(&["dot", "json"]) default_value("dot") "output format"))
+	}
+
+	fn run<'a>(
+		&'a self,
+		matches: &'a ::clap::ArgMatches<'a>,
+		local_api: ::Result<&'a ::factorio_mods_local::API>,
+		_: ::Result<&'a ::factorio_mods_web::API>,
+		_: ::util::GlobalOptions<'a>,
+	) -> Box<Future<Item = (), Error = ::Error> + 'a> {
+		#[cfg_attr(feature = "cargo-clippy", allow(unit_arg))]
+		let result: ::Result<_> = do catch {
+			let local_api = local_api?;
+
+			let graph = DependencyGraph::new(local_api)?;
+
+			match matches.value_of("format").unwrap() {
+				"dot" => graph.print_dot(),
+				"json" => graph.print_json()?,
+				_ => unreachable!(),
+			}
+		};
+
+		Box::new(result.into_future())
+	}
+}
+
+/// The dependency graph of the installed mods.
+///
+/// There is one node per installed mod, plus one node per dependency that isn't installed. Edges point from a mod to its dependencies.
+struct DependencyGraph {
+	graph: ::petgraph::Graph<Node, Edge>,
+	name_to_node_index: ::std::collections::HashMap<::factorio_mods_common::ModName, ::petgraph::graph::NodeIndex>,
+}
+
+enum Node {
+	Installed {
+		installed_mod: ::factorio_mods_local::InstalledMod,
+		enabled: bool,
+	},
+
+	Missing(::factorio_mods_common::ModName),
+}
+
+struct Edge {
+	version: ::factorio_mods_common::ModVersionReq,
+	required: bool,
+	status: EdgeStatus,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ::serde_derive::Serialize)]
+#[serde(rename_all = "snake_case")]
+enum EdgeStatus {
+	/// The dependency is installed and its version satisfies the requirement.
+	Satisfied,
+
+	/// The dependency is installed but its version does not satisfy the requirement.
+	Incompatible,
+
+	/// The dependency is not installed.
+	Missing,
+}
+
+impl Node {
+	fn name(&self) -> &::factorio_mods_common::ModName {
+		match *self {
+			Node::Installed { ref installed_mod, .. } => &installed_mod.info.name,
+			Node::Missing(ref name) => name,
+		}
+	}
+
+	fn installed_mod(&self) -> Option<&::factorio_mods_local::InstalledMod> {
+		match *self {
+			Node::Installed { ref installed_mod, .. } => Some(installed_mod),
+			Node::Missing(_) => None,
+		}
+	}
+}
+
+impl DependencyGraph {
+	fn new(local_api: &::factorio_mods_local::API) -> ::Result<Self> {
+		let mods_status = local_api.mods_status().chain_err(|| "Could not parse installed mods status")?;
+
+		// When more than one version of a mod is installed, use the one that the game loads.
+		let effective_mods = local_api.effective_mods().chain_err(|| "Could not enumerate installed mods")?;
+
+		let mut graph = ::petgraph::Graph::new();
+
+		let mut name_to_node_index: ::std::collections::HashMap<_, _> =
+			effective_mods.into_iter().map(|(name, versions)| {
+				let enabled = mods_status.get(&name).cloned().unwrap_or(true);
+				(name, graph.add_node(Node::Installed { installed_mod: versions.effective, enabled }))
+			}).collect();
+
+		let mut edges_to_add = vec![];
+		for node_index in graph.node_indices() {
+			if let Node::Installed { ref installed_mod, .. } = graph[node_index] {
+				for dep in &installed_mod.info.dependencies {
+					if dep.name.0 != "base" {
+						edges_to_add.push((node_index, dep.clone()));
+					}
+				}
+			}
+		}
+
+		for (node_index, dep) in edges_to_add {
+			let dep_node_index = match name_to_node_index.get(&dep.name) {
+				Some(&dep_node_index) => dep_node_index,
+				None => graph.add_node(Node::Missing(dep.name.clone())),
+			};
+			name_to_node_index.entry(dep.name.clone()).or_insert(dep_node_index);
+
+			let status = match graph[dep_node_index].installed_mod() {
+				Some(dep_mod) if dep.version.0.matches(&dep_mod.info.version.0) => EdgeStatus::Satisfied,
+				Some(_) => EdgeStatus::Incompatible,
+				None => EdgeStatus::Missing,
+			};
+
+			graph.add_edge(node_index, dep_node_index, Edge { version: dep.version, required: dep.required, status });
+		}
+
+		Ok(DependencyGraph { graph, name_to_node_index })
+	}
+
+	/// Returns the outgoing edges of the given node, sorted by the name of the dependency.
+	fn dependencies(&self, node_index: ::petgraph::graph::NodeIndex) -> Vec<(::petgraph::graph::NodeIndex, &Edge)> {
+		let mut result: Vec<_> =
+			self.graph.edges(node_index)
+			.map(|edge| (::petgraph::visit::EdgeRef::target(&edge), edge.weight()))
+			.collect();
+		result.sort_by(|&(node_index1, _), &(node_index2, _)| self.graph[node_index1].name().cmp(self.graph[node_index2].name()));
+		result
+	}
+
+	fn print_tree(
+		&self,
+		node_index: ::petgraph::graph::NodeIndex,
+		indent: &str,
+		ancestors: &mut Vec<::petgraph::graph::NodeIndex>,
+		shown: &mut ::std::collections::HashSet<::petgraph::graph::NodeIndex>,
+	) {
+		if indent.is_empty() {
+			if let Node::Installed { ref installed_mod, enabled } = self.graph[node_index] {
+				println!("{} {}{}", installed_mod.info.name, installed_mod.info.version, if enabled { "" } else { " (disabled)" });
+			}
+		}
+
+		shown.insert(node_index);
+		ancestors.push(node_index);
+
+		let child_indent = format!("{}    ", indent);
+
+		for (dep_node_index, edge) in self.dependencies(node_index) {
+			let dep_node = &self.graph[dep_node_index];
+
+			let status = match (dep_node.installed_mod(), edge.status) {
+				(Some(dep_mod), EdgeStatus::Satisfied) => format!("{} installed, satisfied", dep_mod.info.version),
+				(Some(dep_mod), _) => format!("{} installed, not satisfied", dep_mod.info.version),
+				(None, _) if edge.required => "missing".to_string(),
+				(None, _) => "not installed".to_string(),
+			};
+
+			let suffix =
+				if ancestors.contains(&dep_node_index) {
+					" (cycle)"
+				}
+				else if dep_node.installed_mod().is_some() && shown.contains(&dep_node_index) {
+					" (*)"
+				}
+				else {
+					""
+				};
+
+			println!(
+				"{}{}{} {} ({}){}",
+				child_indent, if edge.required { "" } else { "? " }, dep_node.name(), edge.version, status, suffix);
+
+			if suffix.is_empty() && dep_node.installed_mod().is_some() {
+				self.print_tree(dep_node_index, &child_indent, ancestors, shown);
+			}
+		}
+
+		ancestors.pop();
+	}
+
+	fn print_dot(&self) {
+		println!("digraph mods {{");
+
+		let mut node_indices: Vec<_> = self.graph.node_indices().collect();
+		node_indices.sort_by(|&node_index1, &node_index2| self.graph[node_index1].name().cmp(self.graph[node_index2].name()));
+
+		for &node_index in &node_indices {
+			match self.graph[node_index] {
+				Node::Installed { ref installed_mod, enabled } => println!(
+					"\t{} [label={}{}];",
+					dot_escape(&installed_mod.info.name.0),
+					dot_escape(&format!("{}\n{}", installed_mod.info.name, installed_mod.info.version)),
+					if enabled { "" } else { ", style=filled, fillcolor=lightgray" }),
+
+				Node::Missing(ref name) => println!(
+					"\t{} [label={}, style=dashed, color=red, fontcolor=red];",
+					dot_escape(&name.0),
+					dot_escape(&format!("{}\n(missing)", name))),
+			}
+		}
+
+		for &node_index in &node_indices {
+			for (dep_node_index, edge) in self.dependencies(node_index) {
+				let mut attributes = vec![format!("label={}", dot_escape(&edge.version.to_string()))];
+				if !edge.required {
+					attributes.push("style=dashed".to_string());
+				}
+				match edge.status {
+					EdgeStatus::Satisfied => (),
+					EdgeStatus::Incompatible => attributes.push("color=red".to_string()),
+					EdgeStatus::Missing if edge.required => attributes.push("color=red".to_string()),
+					EdgeStatus::Missing => attributes.push("color=gray".to_string()),
+				}
+
+				println!(
+					"\t{} -> {} [{}];",
+					dot_escape(&self.graph[node_index].name().0),
+					dot_escape(&self.graph[dep_node_index].name().0),
+					attributes.join(", "));
+			}
+		}
+
+		println!("}}");
+	}
+
+	fn print_json(&self) -> ::Result<()> {
+		let mut node_indices: Vec<_> = self.graph.node_indices().collect();
+		node_indices.sort_by(|&node_index1, &node_index2| self.graph[node_index1].name().cmp(self.graph[node_index2].name()));
+
+		let nodes = node_indices.iter().map(|&node_index| match self.graph[node_index] {
+			Node::Installed { ref installed_mod, enabled } => JsonNode {
+				name: &installed_mod.info.name,
+				version: Some(&installed_mod.info.version),
+				installed: true,
+				enabled: Some(enabled),
+			},
+
+			Node::Missing(ref name) => JsonNode {
+				name,
+				version: None,
+				installed: false,
+				enabled: None,
+			},
+		}).collect();
+
+		let edges = node_indices.iter().flat_map(|&node_index|
+			self.dependencies(node_index).into_iter().map(move |(dep_node_index, edge)| JsonEdge {
+				from: self.graph[node_index].name(),
+				to: self.graph[dep_node_index].name(),
+				version: &edge.version,
+				required: edge.required,
+				status: edge.status,
+			}))
+			.collect();
+
+		::output::print_json(&JsonGraph { nodes, edges })
+	}
+}
+
+/// The JSON representation of the dependency graph.
+#[derive(::serde_derive::Serialize)]
+struct JsonGraph<'a> {
+	nodes: Vec<JsonNode<'a>>,
+	edges: Vec<JsonEdge<'a>>,
+}
+
+/// The JSON representation of a mod in the dependency graph.
+#[derive(::serde_derive::Serialize)]
+struct JsonNode<'a> {
+	name: &'a ::factorio_mods_common::ModName,
+
+	/// `null` if the mod is not installed.
+	version: Option<&'a ::factorio_mods_common::ReleaseVersion>,
+
+	installed: bool,
+
+	/// `null` if the mod is not installed.
+	enabled: Option<bool>,
+}
+
+/// The JSON representation of a dependency in the dependency graph.
+#[derive(::serde_derive::Serialize)]
+struct JsonEdge<'a> {
+	from: &'a ::factorio_mods_common::ModName,
+	to: &'a ::factorio_mods_common::ModName,
+	version: &'a ::factorio_mods_common::ModVersionReq,
+	required: bool,
+	status: EdgeStatus,
+}
+
+/// Quotes the given string as a DOT identifier.
+fn dot_escape(s: &str) -> String {
+	let mut result = String::with_capacity(s.len() + 2);
+	result.push('"');
+	for c in s.chars() {
+		match c {
+			'"' => result.push_str(r#"\""#),
+			'\\' => result.push_str(r"\\"),
+			'\n' => result.push_str(r"\n"),
+			c => result.push(c),
+		}
+	}
+	result.push('"');
+	result
+}
@@ -18,7 +18,7 @@ impl ::util::SubCommand for SubCommand {
 		matches: &'a ::clap::ArgMatches<'a>,
 		local_api: ::Result<&'a ::factorio_mods_local::API>,
 		web_api: ::Result<&'a ::factorio_mods_web::API>,
-		prompt_override: Option<bool>,
+		global_options: ::util::GlobalOptions<'a>,
 	) -> Box<Future<Item = (), Error = ::Error> + 'a> {
 		use ::ResultExt;
 
@@ -28,7 +28,7 @@ impl ::util::SubCommand for SubCommand {
 			let local_api = local_api?;
 			let web_api = web_api?;
 
-			let mut config = ::config::Config::load(local_api)?;
+			let mut config = ::config::Config::load(local_api, global_options.wait_for_lock)?;
 
 			for requirement in requirements {
 				let captures = match REQUIREMENT_REGEX.captures(requirement) {
@@ -45,7 +45,7 @@ impl ::util::SubCommand for SubCommand {
 				config.mods.insert(name, ::factorio_mods_common::ModVersionReq(requirement));
 			}
 
-			::await!(::solve::compute_and_apply_diff(local_api, web_api, config, prompt_override))?;
+			::await!(::solve::compute_and_apply_diff(local_api, web_api, config, global_options))?;
 
 			Ok(())
 		})
@@ -0,0 +1,253 @@
+//! Installing mods from the cache into the mods directory by copying or linking them.
+
+/// How a cached mod is installed into the mods directory.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InstallStrategy {
+	/// Copy the file.
+	Copy,
+
+	/// Create a hard link to the file. Only possible if the cache and the mods directory are on the same filesystem.
+	Hardlink,
+
+	/// Create a symbolic link to the file. On Windows, this requires Developer Mode or administrator privileges.
+	Symlink,
+
+	/// Clone the file with copy-on-write. Only possible on Linux filesystems that support it, such as btrfs and XFS.
+	Reflink,
+}
+
+impl InstallStrategy {
+	/// Parses the value of the `--install-strategy` option.
+	pub fn parse(s: &str) -> Option<Self> {
+		match s {
+			"copy" => Some(InstallStrategy::Copy),
+			"hardlink" => Some(InstallStrategy::Hardlink),
+			"symlink" => Some(InstallStrategy::Symlink),
+			"reflink" => Some(InstallStrategy::Reflink),
+			_ => None,
+		}
+	}
+
+	/// A description of this strategy for progress messages, such as "copying"
+	pub fn description(self) -> &'static str {
+		match self {
+			InstallStrategy::Copy => "copying",
+			InstallStrategy::Hardlink => "hard-linking",
+			InstallStrategy::Symlink => "symlinking",
+			InstallStrategy::Reflink => "reflinking",
+		}
+	}
+}
+
+/// Installs the given cached file to the given target path with the given strategy.
+///
+/// If the strategy is not supported, for example because the cache and the mods directory are on different filesystems,
+/// the file is copied instead and the error that prevented the strategy is returned. Any other error, such as the target already existing,
+/// fails the install.
+pub fn install(source: &::std::path::Path, target: &::std::path::Path, strategy: InstallStrategy) -> ::Result<Option<::std::io::Error>> {
+	use ::ResultExt;
+
+	let result = match strategy {
+		InstallStrategy::Copy => Ok(()),
+		InstallStrategy::Hardlink => ::std::fs::hard_link(source, target),
+		InstallStrategy::Symlink => symlink_file(source, target),
+		InstallStrategy::Reflink => reflink(source, target),
+	};
+
+	let unsupported = match result {
+		Ok(()) if strategy != InstallStrategy::Copy => return Ok(None),
+
+		Ok(()) => None,
+
+		Err(err) =>
+			if is_unsupported(strategy, &err) {
+				Some(err)
+			}
+			else {
+				return Err(err).chain_err(|| format!("Could not install {} to {} by {}", source.display(), target.display(), strategy.description()));
+			},
+	};
+
+	let _ =
+		::std::fs::copy(source, target)
+		.chain_err(|| format!("Could not copy file {} to {}", source.display(), target.display()))?;
+
+	Ok(unsupported)
+}
+
+/// Removes the given installed mod.
+///
+/// If the mod is a symbolic link, only the link is removed and not the file or directory it points to.
+pub fn remove(path: &::std::path::Path) -> ::Result<()> {
+	use ::ResultExt;
+
+	let metadata = ::std::fs::symlink_metadata(path).chain_err(|| format!("Could not read metadata of {}", path.display()))?;
+
+	if metadata.file_type().is_symlink() {
+		remove_symlink(path).chain_err(|| format!("Could not remove link {}", path.display()))
+	}
+	else if metadata.is_dir() {
+		::std::fs::remove_dir_all(path).chain_err(|| format!("Could not remove directory {}", path.display()))
+	}
+	else {
+		::std::fs::remove_file(path).chain_err(|| format!("Could not remove file {}", path.display()))
+	}
+}
+
+/// Creates a symbolic link at the given target path to the given directory, such as the working copy of a mod that is being developed.
+pub fn symlink_directory(source: &::std::path::Path, target: &::std::path::Path) -> ::Result<()> {
+	use ::ResultExt;
+
+	symlink_dir(source, target).chain_err(|| format!("Could not create link {} to {}", target.display(), source.display()))
+}
+
+/// Whether an installed mod is linked to another file or directory.
+#[derive(Debug)]
+pub enum LinkState {
+	/// The mod is not a link, or is a reflink. Reflinks are indistinguishable from copies.
+	NotLinked,
+
+	/// The mod is a file with other hard links, such as the file in the cache that it was installed from.
+	Hardlink,
+
+	/// The mod is a symbolic link to the given path.
+	Symlink(::std::path::PathBuf),
+}
+
+impl LinkState {
+	/// Determines the link state of the given installed mod.
+	pub fn of(path: &::std::path::Path) -> ::Result<Self> {
+		use ::ResultExt;
+
+		let metadata = ::std::fs::symlink_metadata(path).chain_err(|| format!("Could not read metadata of {}", path.display()))?;
+
+		if metadata.file_type().is_symlink() {
+			let target = ::std::fs::read_link(path).chain_err(|| format!("Could not read link {}", path.display()))?;
+			Ok(LinkState::Symlink(target))
+		}
+		else if metadata.is_file() && num_links(&metadata) > 1 {
+			Ok(LinkState::Hardlink)
+		}
+		else {
+			Ok(LinkState::NotLinked)
+		}
+	}
+
+	/// A short name of this state, for `fac list`. `None` if the mod is not linked.
+	pub fn name(&self) -> Option<&'static str> {
+		match *self {
+			LinkState::NotLinked => None,
+			LinkState::Hardlink => Some("hardlink"),
+			LinkState::Symlink(_) => Some("symlink"),
+		}
+	}
+}
+
+#[cfg(unix)]
+fn symlink_file(source: &::std::path::Path, target: &::std::path::Path) -> ::std::io::Result<()> {
+	::std::os::unix::fs::symlink(source, target)
+}
+
+#[cfg(windows)]
+fn symlink_file(source: &::std::path::Path, target: &::std::path::Path) -> ::std::io::Result<()> {
+	::std::os::windows::fs::symlink_file(source, target)
+}
+
+#[cfg(unix)]
+fn symlink_dir(source: &::std::path::Path, target: &::std::path::Path) -> ::std::io::Result<()> {
+	::std::os::unix::fs::symlink(source, target)
+}
+
+#[cfg(windows)]
+fn symlink_dir(source: &::std::path::Path, target: &::std::path::Path) -> ::std::io::Result<()> {
+	::std::os::windows::fs::symlink_dir(source, target)
+}
+
+#[cfg(unix)]
+fn remove_symlink(path: &::std::path::Path) -> ::std::io::Result<()> {
+	::std::fs::remove_file(path)
+}
+
+#[cfg(windows)]
+fn remove_symlink(path: &::std::path::Path) -> ::std::io::Result<()> {
+	// Symbolic links to directories are themselves directories on Windows.
+	::std::fs::remove_file(path).or_else(|_| ::std::fs::remove_dir(path))
+}
+
+/// Returns whether the given error from installing a file with the given strategy means that the strategy isn't supported
+/// for the cache and the mods directory, so the file should be copied instead.
+#[cfg(target_os = "linux")]
+fn is_unsupported(strategy: InstallStrategy, err: &::std::io::Error) -> bool {
+	match err.raw_os_error() {
+		Some(::libc::EXDEV) | Some(::libc::EOPNOTSUPP) | Some(::libc::EINVAL) => true,
+
+		// The filesystem doesn't implement FICLONE
+		Some(::libc::ENOTTY) => strategy == InstallStrategy::Reflink,
+
+		_ => false,
+	}
+}
+
+/// Returns whether the given error from installing a file with the given strategy means that the strategy isn't supported
+/// for the cache and the mods directory, so the file should be copied instead.
+#[cfg(all(unix, not(target_os = "linux")))]
+fn is_unsupported(strategy: InstallStrategy, err: &::std::io::Error) -> bool {
+	// Reflinks are only supported on Linux.
+	strategy == InstallStrategy::Reflink ||
+	match err.raw_os_error() {
+		// EXDEV, EINVAL, and EOPNOTSUPP on macOS and the BSDs respectively
+		Some(18) | Some(22) | Some(102) | Some(45) => true,
+		_ => false,
+	}
+}
+
+/// Returns whether the given error from installing a file with the given strategy means that the strategy isn't supported
+/// for the cache and the mods directory, so the file should be copied instead.
+#[cfg(windows)]
+fn is_unsupported(strategy: InstallStrategy, err: &::std::io::Error) -> bool {
+	// Reflinks are only supported on Linux.
+	strategy == InstallStrategy::Reflink ||
+	match err.raw_os_error() {
+		// ERROR_INVALID_FUNCTION, ERROR_NOT_SAME_DEVICE, ERROR_PRIVILEGE_NOT_HELD
+		Some(1) | Some(17) | Some(1314) => true,
+		_ => false,
+	}
+}
+
+#[cfg(unix)]
+fn num_links(metadata: &::std::fs::Metadata) -> u64 {
+	::std::os::unix::fs::MetadataExt::nlink(metadata)
+}
+
+#[cfg(windows)]
+fn num_links(_: &::std::fs::Metadata) -> u64 {
+	// The number of links of a file is not exposed on Windows.
+	1
+}
+
+#[cfg(target_os = "linux")]
+fn reflink(source: &::std::path::Path, target: &::std::path::Path) -> ::std::io::Result<()> {
+	use ::std::os::unix::io::AsRawFd;
+
+	/// `_IOW(0x94, 9, int)` from `linux/fs.h`
+	const FICLONE: ::libc::c_ulong = 0x4004_9409;
+
+	let source_file = ::std::fs::File::open(source)?;
+	let target_file = ::std::fs::OpenOptions::new().write(true).create_new(true).open(target)?;
+
+	#[cfg_attr(feature = "cargo-clippy", allow(cast_possible_truncation, useless_attribute))]
+	let result = unsafe { ::libc::ioctl(target_file.as_raw_fd(), FICLONE as _, source_file.as_raw_fd()) };
+	if result == -1 {
+		let err = ::std::io::Error::last_os_error();
+		drop(target_file);
+		let _ = ::std::fs::remove_file(target);
+		return Err(err);
+	}
+
+	Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_: &::std::path::Path, _: &::std::path::Path) -> ::std::io::Result<()> {
+	Err(::std::io::Error::new(::std::io::ErrorKind::Other, "reflinks are only supported on Linux"))
+}
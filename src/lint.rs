@@ -0,0 +1,345 @@
+use ::futures::Future;
+
+use ::ResultExt;
+
+/// Fields of info.json that every mod must have.
+///
+/// The game treats `factorio_version` as optional, but then assumes the mod is for 0.12, which no recent version of the game loads.
+const REQUIRED_FIELDS: &[&str] = &["name", "version", "title", "author", "factorio_version"];
+
+/// Optional fields of info.json that must be strings if they're present.
+const OPTIONAL_STRING_FIELDS: &[&str] = &["description", "homepage", "contact"];
+
+/// The prefixes of dependencies that the game understands: incompatible, optional, hidden optional, and doesn't affect load order.
+const DEPENDENCY_PREFIXES: &[&str] = &["!", "(?)", "?", "~"];
+
+const DEPENDENCY_OPERATORS: &[&str] = &["<", "<=", "=", ">=", ">"];
+
+pub struct SubCommand;
+
+impl ::util::SubCommand for SubCommand {
+	fn build_subcommand<'a>(&self, subcommand: ::clap::App<'a, 'a>) -> ::clap::App<'a, 'a> {
+		clap_app!(@app (subcommand)
+			(about: "Check the info.json of a mod for mistakes.\n\n\
+			         Reports missing required fields, versions that aren't in canonical form, dependencies with unknown prefixes, \
+			         folder or zip names that don't match the name and version of the mod, names with characters that the mods portal \
+			         doesn't allow, and dependencies on mods that don't exist on the mods portal.")
+			(@arg path: +required "path of the mod's directory or zip file"))
+	}
+
+	fn run<'a>(
+		&'a self,
+		matches: &'a ::clap::ArgMatches<'a>,
+		_: ::Result<&'a ::factorio_mods_local::API>,
+		web_api: ::Result<&'a ::factorio_mods_web::API>,
+		_: ::util::GlobalOptions<'a>,
+	) -> Box<Future<Item = (), Error = ::Error> + 'a> {
+		Box::new(::async_block! {
+			let path = ::std::path::Path::new(matches.value_of("path").unwrap());
+
+			let (mut problems, dependencies) = lint(path)?;
+
+			if !dependencies.is_empty() {
+				let web_api = web_api?;
+
+				let mods =
+					::await!(web_api.get_many(&dependencies))
+					.chain_err(|| "Could not look up dependencies on the mods portal")?;
+				let found: ::std::collections::HashSet<_> = mods.into_iter().map(|mod_| mod_.name).collect();
+
+				for dependency in &dependencies {
+					if !found.contains(dependency) {
+						problems.push(format!("Dependency {} does not exist on the mods portal", dependency));
+					}
+				}
+			}
+
+			if problems.is_empty() {
+				println!("No problems found in {}", path.display());
+				return Ok(());
+			}
+
+			println!("{}:", path.display());
+			for problem in &problems {
+				println!("    {}", problem);
+			}
+
+			bail!("Found {} {}", problems.len(), if problems.len() == 1 { "problem" } else { "problems" })
+		})
+	}
+}
+
+/// Checks the info.json of the mod at the given path.
+///
+/// Returns the problems that were found, and the names of the mods it depends on so that they can be looked up on the mods portal.
+fn lint(path: &::std::path::Path) -> ::Result<(Vec<String>, Vec<::factorio_mods_common::ModName>)> {
+	let (contents, zip_toplevel) = read_info_json(path)?;
+
+	let info: ::serde_json::Value = ::serde_json::from_str(&contents).chain_err(|| "Could not parse info.json")?;
+	let info = info.as_object().ok_or("info.json does not contain a JSON object")?;
+
+	let mut problems = vec![];
+
+	for &field in REQUIRED_FIELDS {
+		match info.get(field) {
+			None => problems.push(format!("Missing required field {}", field)),
+			Some(&::serde_json::Value::String(_)) => (),
+			Some(_) => problems.push(format!("Field {} must be a string", field)),
+		}
+	}
+
+	for &field in OPTIONAL_STRING_FIELDS {
+		match info.get(field) {
+			None | Some(&::serde_json::Value::String(_)) => (),
+			Some(_) => problems.push(format!("Field {} must be a string", field)),
+		}
+	}
+
+	let name = info.get("name").and_then(::serde_json::Value::as_str);
+	let version = info.get("version").and_then(::serde_json::Value::as_str);
+
+	if let Some(name) = name {
+		if !::util::is_valid_mod_name(name) {
+			problems.push(format!(r#"Name "{}" must only contain up to 100 letters, digits, dashes and underscores"#, name));
+		}
+	}
+
+	if let Some(version) = version {
+		if !is_canonical_version(version, &[3]) {
+			problems.push(format!(r#"Version "{}" must be of the form major.minor.patch, with numbers from 0 to 65535 without leading zeros"#, version));
+		}
+	}
+
+	if let Some(factorio_version) = info.get("factorio_version").and_then(::serde_json::Value::as_str) {
+		if !is_canonical_version(factorio_version, &[2]) {
+			problems.push(format!(r#"Game version "{}" must be of the form major.minor, such as "0.16""#, factorio_version));
+		}
+	}
+
+	if let (Some(name), Some(version)) = (name, version) {
+		// Paths like `.` don't have a file name of their own. Other paths are not canonicalized, since that would resolve links
+		// such as those created by `fac dev link`, whose names are the ones that matter.
+		let path = match path.file_name() {
+			Some(_) => path.to_path_buf(),
+			None => path.canonicalize().chain_err(|| format!("Could not canonicalize {}", path.display()))?,
+		};
+
+		check_file_name(&path, zip_toplevel.as_ref().map(String::as_str), name, version, &mut problems);
+	}
+
+	let mut dependencies = vec![];
+
+	match info.get("dependencies") {
+		None => (),
+
+		Some(&::serde_json::Value::Array(ref values)) =>
+			for value in values {
+				match value.as_str() {
+					Some(dependency) =>
+						if let Some(name) = check_dependency(dependency, &mut problems) {
+							if name.0 != "base" && !dependencies.contains(&name) {
+								dependencies.push(name);
+							}
+						},
+
+					None => problems.push(format!("Dependency {} must be a string", value)),
+				}
+			},
+
+		Some(_) => problems.push("Field dependencies must be an array of strings".to_string()),
+	}
+
+	Ok((problems, dependencies))
+}
+
+/// Reads the contents of the info.json of the mod at the given path.
+///
+/// If the mod is zipped, the name of the top-level directory in the zip is also returned.
+fn read_info_json(path: &::std::path::Path) -> ::Result<(String, Option<String>)> {
+	if path.is_dir() {
+		let info_json_file_path = path.join("info.json");
+		let contents = ::std::fs::read_to_string(&info_json_file_path).chain_err(|| format!("Could not read {}", info_json_file_path.display()))?;
+		return Ok((contents, None));
+	}
+
+	let zip_file = ::std::fs::File::open(path).chain_err(|| format!("Could not open {} for reading", path.display()))?;
+	let mut zip_file = ::zip::ZipArchive::new(zip_file).chain_err(|| format!("Could not read {} as a zip file", path.display()))?;
+
+	ensure!(zip_file.len() != 0, "{} is empty", path.display());
+
+	let toplevel = {
+		let first_file = zip_file.by_index(0).chain_err(|| format!("Could not read {} as a zip file", path.display()))?;
+		first_file.name().split('/').next().unwrap().to_string()
+	};
+
+	let mut contents = String::new();
+	{
+		let mut info_json_file = zip_file.by_name(&format!("{}/info.json", toplevel)).chain_err(|| format!("Could not find {}/info.json in {}", toplevel, path.display()))?;
+		let _ = ::std::io::Read::read_to_string(&mut info_json_file, &mut contents).chain_err(|| format!("Could not read {}/info.json in {}", toplevel, path.display()))?;
+	}
+
+	Ok((contents, Some(toplevel)))
+}
+
+/// Checks that the name of the mod's directory or zip file, and the top-level directory in the zip, match the mod's name and version.
+fn check_file_name(path: &::std::path::Path, zip_toplevel: Option<&str>, name: &str, version: &str, problems: &mut Vec<String>) {
+	let expected = format!("{}_{}", name, version);
+
+	let file_name = path.file_name().map_or_else(Default::default, |file_name| file_name.to_string_lossy());
+
+	match zip_toplevel {
+		Some(toplevel) => {
+			let expected_file_name = format!("{}.zip", expected);
+			if file_name != expected_file_name {
+				problems.push(format!("Zip file is named {} but should be named {}", file_name, expected_file_name));
+			}
+
+			if toplevel != expected && toplevel != name {
+				problems.push(format!("Directory in the zip file is named {} but should be named {}", toplevel, expected));
+			}
+		},
+
+		// The game also allows unpacked mods to be in a directory named after just the mod.
+		None =>
+			if file_name != expected && file_name != name {
+				problems.push(format!("Directory is named {} but should be named {} or {}", file_name, expected, name));
+			},
+	}
+}
+
+/// Checks the given dependency string, and returns the name of the mod it refers to if it could be parsed.
+fn check_dependency(dependency: &str, problems: &mut Vec<String>) -> Option<::factorio_mods_common::ModName> {
+	let trimmed = dependency.trim();
+
+	let rest = match DEPENDENCY_PREFIXES.iter().find(|prefix| trimmed.starts_with(**prefix)) {
+		Some(prefix) => trimmed[prefix.len()..].trim_left(),
+
+		None => {
+			let unknown_prefix: String = trimmed.chars().take_while(|&c| !c.is_alphanumeric() && c != '-' && c != '_' && !c.is_whitespace()).collect();
+			if !unknown_prefix.is_empty() {
+				problems.push(format!(r#"Dependency "{}" has unknown prefix "{}". The known prefixes are ! ? (?) and ~"#, dependency, unknown_prefix));
+				return None;
+			}
+
+			trimmed
+		},
+	};
+
+	let (name, version_req) = match rest.find(|c: char| c == '<' || c == '>' || c == '=') {
+		Some(index) => (rest[..index].trim(), Some(&rest[index..])),
+		None => (rest, None),
+	};
+
+	if name.is_empty() {
+		problems.push(format!(r#"Dependency "{}" does not have a mod name"#, dependency));
+		return None;
+	}
+
+	if let Some(version_req) = version_req {
+		let operator_len = version_req.find(|c: char| c != '<' && c != '>' && c != '=').unwrap_or_else(|| version_req.len());
+		let (operator, version) = version_req.split_at(operator_len);
+		let version = version.trim();
+
+		if !DEPENDENCY_OPERATORS.contains(&operator) {
+			problems.push(format!(r#"Dependency "{}" has unknown operator "{}""#, dependency, operator));
+		}
+		else if !is_canonical_version(version, &[2, 3]) {
+			problems.push(format!(r#"Dependency "{}" has version "{}" which must be of the form major.minor.patch or major.minor"#, dependency, version));
+		}
+	}
+
+	Some(::factorio_mods_common::ModName(name.to_string()))
+}
+
+/// Returns whether the given version has one of the given numbers of parts, each of which is a number from 0 to 65535 without leading zeros.
+fn is_canonical_version(version: &str, num_parts: &[usize]) -> bool {
+	let parts: Vec<_> = version.split('.').collect();
+
+	num_parts.contains(&parts.len()) &&
+	parts.iter().all(|part|
+		!part.is_empty() &&
+		part.chars().all(|c| c.is_ascii_digit()) &&
+		(*part == "0" || !part.starts_with('0')) &&
+		part.parse::<u16>().is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_check_dependency() {
+		for &(dependency, expected_name, expected_num_problems) in &[
+			("foo", Some("foo"), 0),
+			("! foo", Some("foo"), 0),
+			("? foo", Some("foo"), 0),
+			("?foo", Some("foo"), 0),
+			("(?) foo", Some("foo"), 0),
+			("~ foo", Some("foo"), 0),
+			("+ foo", None, 1),
+			("(foo)", None, 1),
+			("!", None, 1),
+
+			("foo < 1.0.0", Some("foo"), 0),
+			("foo <= 1.0.0", Some("foo"), 0),
+			("foo = 1.0", Some("foo"), 0),
+			("? foo >= 0.1.0", Some("foo"), 0),
+			("foo>1.0.0", Some("foo"), 0),
+			("foo == 1.0.0", Some("foo"), 1),
+			("foo => 1.0.0", Some("foo"), 1),
+			("foo >= 1", Some("foo"), 1),
+			("foo >= 1.0.0.0", Some("foo"), 1),
+			("foo >= 01.0.0", Some("foo"), 1),
+			(">= 1.0.0", None, 1),
+		] {
+			let mut problems = vec![];
+			let name = check_dependency(dependency, &mut problems);
+			assert_eq!(name.as_ref().map(|name| &*name.0), expected_name, "{}", dependency);
+			assert_eq!(problems.len(), expected_num_problems, "{} {:?}", dependency, problems);
+		}
+	}
+
+	#[test]
+	fn test_is_canonical_version() {
+		for &(version, num_parts, expected) in &[
+			("1.2.3", &[3][..], true),
+			("0.0.0", &[3][..], true),
+			("1.2.65535", &[3][..], true),
+			("1.2.65536", &[3][..], false),
+			("01.2.3", &[3][..], false),
+			("1.2.00", &[3][..], false),
+			("1.2", &[3][..], false),
+			("1.2.3", &[2][..], false),
+			("0.16", &[2][..], true),
+			("1.2", &[2, 3][..], true),
+			("1.2.3", &[2, 3][..], true),
+			("1", &[2, 3][..], false),
+			("1..3", &[3][..], false),
+			("1.2.a", &[3][..], false),
+			("1.2.+3", &[3][..], false),
+			("", &[3][..], false),
+		] {
+			assert_eq!(is_canonical_version(version, num_parts), expected, "{} {:?}", version, num_parts);
+		}
+	}
+
+	#[test]
+	fn test_check_file_name() {
+		for &(path, zip_toplevel, expected_num_problems) in &[
+			("mods/foo_1.0.0.zip", Some("foo_1.0.0"), 0),
+			("foo_1.0.0.zip", Some("foo"), 0),
+			("foo.zip", Some("foo_1.0.0"), 1),
+			("foo_1.0.0.zip", Some("bar"), 1),
+			("foo_1.0.1.zip", Some("foo_1.0.1"), 2),
+
+			("mods/foo_1.0.0", None, 0),
+			("foo", None, 0),
+			("foo_1.0.1", None, 1),
+			("foo_1.0.0.zip", None, 1),
+		] {
+			let mut problems = vec![];
+			check_file_name(::std::path::Path::new(path), zip_toplevel, "foo", "1.0.0", &mut problems);
+			assert_eq!(problems.len(), expected_num_problems, "{} {:?} {:?}", path, zip_toplevel, problems);
+		}
+	}
+}
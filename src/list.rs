@@ -13,7 +13,7 @@ impl ::util::SubCommand for SubCommand {
 		_: &'a ::clap::ArgMatches<'a>,
 		local_api: ::Result<&'a ::factorio_mods_local::API>,
 		_: ::Result<&'a ::factorio_mods_web::API>,
-		_: Option<bool>,
+		global_options: ::util::GlobalOptions<'a>,
 	) -> Box<Future<Item = (), Error = ::Error> + 'a> {
 		use ::ResultExt;
 
@@ -23,22 +23,41 @@ impl ::util::SubCommand for SubCommand {
 
 			let mods_status = local_api.mods_status().chain_err(|| "Could not parse installed mods status")?;
 
-			let installed_mods: Result<Vec<_>, _> =
-				local_api.installed_mods()
-				.chain_err(|| "Could not enumerate installed mods")?
-				.map(|installed_mod| installed_mod.map(|installed_mod| {
-					let enabled = mods_status.get(&installed_mod.info.name).cloned().unwrap_or(true);
-					(installed_mod, enabled)
-				}))
-				.collect();
-			let mut installed_mods = installed_mods.chain_err(|| "Could not enumerate installed mods")?;
-			if installed_mods.is_empty() {
+			let effective_mods = local_api.effective_mods().chain_err(|| "Could not enumerate installed mods")?;
+
+			let mut installed_mods = vec![];
+			for (name, versions) in effective_mods {
+				let enabled = mods_status.get(&name).cloned().unwrap_or(true);
+				let effective_version = versions.effective.info.version.clone();
+
+				for shadowed in versions.shadowed {
+					let link_state = ::link::LinkState::of(&shadowed.path)?;
+					installed_mods.push((shadowed, enabled, link_state, Some(effective_version.clone())));
+				}
+
+				let link_state = ::link::LinkState::of(&versions.effective.path)?;
+				installed_mods.push((versions.effective, enabled, link_state, None));
+			}
+
+			if let ::util::OutputFormat::Json = global_options.output_format {
+				installed_mods.sort_by(|m1, m2|
+					m1.0.info.name.cmp(&m2.0.info.name)
+					.then_with(|| m1.0.info.version.cmp(&m2.0.info.version)));
+
+				let records: Vec<_> =
+					installed_mods.iter()
+					.map(|&(ref installed_mod, enabled, ref link_state, ref shadowed_by)| ::output::InstalledModRecord::new(installed_mod, enabled, link_state, shadowed_by.as_ref()))
+					.collect();
+				::output::print_json(&records)?;
+			}
+			else if installed_mods.is_empty() {
 				println!("No installed mods.");
 			}
 			else {
 				installed_mods.sort_by(|m1, m2|
 					m1.1.cmp(&m2.1).reverse()
-					.then_with(|| m1.0.info.name.cmp(&m2.0.info.name)));
+					.then_with(|| m1.0.info.name.cmp(&m2.0.info.name))
+					.then_with(|| m1.0.info.version.cmp(&m2.0.info.version)));
 
 				let installed_mods = installed_mods;
 
@@ -47,10 +66,18 @@ impl ::util::SubCommand for SubCommand {
 				for installed_mod in installed_mods {
 					let mut tags = vec![];
 					if !installed_mod.1 {
-						tags.push("disabled");
+						tags.push("disabled".to_string());
 					}
 					if let ::factorio_mods_local::InstalledModType::Unpacked = installed_mod.0.mod_type {
-						tags.push("unpacked");
+						tags.push("unpacked".to_string());
+					}
+					if let Some(ref shadowed_by) = installed_mod.3 {
+						tags.push(format!("shadowed by {}", shadowed_by));
+					}
+					match installed_mod.2 {
+						::link::LinkState::NotLinked => (),
+						::link::LinkState::Hardlink => tags.push("hardlink".to_string()),
+						::link::LinkState::Symlink(ref target) => tags.push(format!("symlink to {}", target.display())),
 					}
 
 					let tags_string = if tags.is_empty() { String::new() } else { format!(" ({})", tags.join(", ")) };
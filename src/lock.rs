@@ -0,0 +1,82 @@
+//! A lock that prevents multiple instances of fac from modifying the mods directory, `mod-list.json`, the config and the cache at the same time.
+
+use ::ResultExt;
+
+/// An exclusive advisory lock on the `fac.lock` file in the cache directory, held until dropped.
+///
+/// The cache is shared by all Factorio installs, so a single lock in the cache directory covers both the cache and the mods directory.
+///
+/// Acquiring the lock again while this process already holds it shares the same lock, which is released when all of them have been dropped.
+#[derive(Debug)]
+pub struct Lock {
+	file: ::std::rc::Rc<::std::fs::File>,
+}
+
+thread_local! {
+	/// The lock file, while this process holds the lock.
+	static HELD: ::std::cell::RefCell<::std::rc::Weak<::std::fs::File>> = ::std::cell::RefCell::new(::std::rc::Weak::new());
+}
+
+impl Lock {
+	/// Acquires the lock. If another instance of fac holds it, either waits for it to be released or fails,
+	/// depending on `wait`.
+	///
+	/// Also warns if Factorio appears to be running against the mods directory of the given local API.
+	pub fn acquire(local_api: Option<&::factorio_mods_local::API>, wait: bool) -> ::Result<Self> {
+		if let Some(file) = HELD.with(|held| held.borrow().upgrade()) {
+			return Ok(Lock { file });
+		}
+
+		let cache_directory = ::config::cache_directory()?;
+		::std::fs::create_dir_all(&cache_directory).chain_err(|| format!("Could not create cache directory {}", cache_directory.display()))?;
+
+		let lock_file_path = cache_directory.join("fac.lock");
+		// The PID is kept in a separate file because Windows doesn't allow reading a file that another process has locked.
+		let pid_file_path = cache_directory.join("fac.pid");
+
+		let file =
+			::std::fs::OpenOptions::new().write(true).create(true).open(&lock_file_path)
+			.chain_err(|| format!("Could not open lock file {}", lock_file_path.display()))?;
+
+		if let Err(err) = ::fs2::FileExt::try_lock_exclusive(&file) {
+			if err.raw_os_error() != ::fs2::lock_contended_error().raw_os_error() {
+				return Err(err).chain_err(|| format!("Could not lock {}", lock_file_path.display()));
+			}
+
+			let message = match ::std::fs::read_to_string(&pid_file_path) {
+				Ok(ref pid) if !pid.trim().is_empty() => format!("Another instance of fac is running (pid {})", pid.trim()),
+				_ => "Another instance of fac is running".to_string(),
+			};
+
+			ensure!(wait, "{}. Wait for it to finish, or run with --wait to wait for it automatically.", message);
+
+			eprintln!("{}. Waiting for it to finish ...", message);
+
+			::fs2::FileExt::lock_exclusive(&file).chain_err(|| format!("Could not lock {}", lock_file_path.display()))?;
+		}
+
+		::std::fs::write(&pid_file_path, ::std::process::id().to_string()).chain_err(|| format!("Could not write to {}", pid_file_path.display()))?;
+
+		if let Some(local_api) = local_api {
+			if local_api.is_game_running() {
+				eprintln!(
+					"Warning: Factorio appears to be running and using the mods directory {}. \
+					 Changes to mods will not take effect until it is restarted, and it may overwrite mod-list.json when it exits.",
+					local_api.mods_directory().display());
+			}
+		}
+
+		let file = ::std::rc::Rc::new(file);
+		HELD.with(|held| *held.borrow_mut() = ::std::rc::Rc::downgrade(&file));
+
+		Ok(Lock { file })
+	}
+}
+
+impl Drop for Lock {
+	fn drop(&mut self) {
+		if ::std::rc::Rc::strong_count(&self.file) == 1 {
+			let _ = ::fs2::FileExt::unlock(&*self.file);
+		}
+	}
+}
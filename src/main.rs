@@ -22,10 +22,14 @@ extern crate error_chain;
 extern crate factorio_mods_common;
 extern crate factorio_mods_local;
 extern crate factorio_mods_web;
+extern crate fs2;
 extern crate futures_await as futures;
+extern crate globset;
 extern crate itertools;
 #[macro_use]
 extern crate lazy_static;
+#[cfg(target_os = "linux")]
+extern crate libc;
 extern crate multimap;
 extern crate package;
 extern crate petgraph;
@@ -36,21 +40,38 @@ extern crate semver;
 extern crate serde;
 extern crate serde_derive;
 extern crate serde_json;
+extern crate sha1;
 extern crate term_size;
 extern crate textwrap;
+extern crate time;
+extern crate zip;
 
 use factorio_mods_web::reqwest;
 use futures::prelude::{ async_block, await };
 
+mod apply_plan;
+mod cache;
+mod dedupe;
+mod dev;
 mod enable_disable;
+mod graph;
 mod install;
+mod lint;
 mod list;
+mod outdated;
+mod pack;
 mod remove;
 mod search;
 mod show;
 mod update;
 
 mod config;
+mod download;
+mod link;
+mod lock;
+mod output;
+mod plan;
+mod search_index;
 mod solve;
 mod util;
 
@@ -64,22 +85,40 @@ quick_main!(|| -> Result<()> {
 
 	// Run everything in a separate thread because the default Windows main thread stack isn't big enough (1 MiB)
 	::std::thread::spawn(|| {
+		let apply_plan_subcommand = apply_plan::SubCommand;
+		let cache_subcommand = cache::SubCommand;
+		let dedupe_subcommand = dedupe::SubCommand;
+		let dev_subcommand = dev::SubCommand;
 		let disable_subcommand = enable_disable::DisableSubCommand;
 		let enable_subcommand = enable_disable::EnableSubCommand;
+		let graph_subcommand = graph::GraphSubCommand;
 		let install_subcommand = install::SubCommand;
+		let lint_subcommand = lint::SubCommand;
 		let list_subcommand = list::SubCommand;
+		let outdated_subcommand = outdated::SubCommand;
+		let pack_subcommand = pack::SubCommand;
 		let remove_subcommand = remove::SubCommand;
 		let search_subcommand = search::SubCommand;
 		let show_subcommand = show::SubCommand;
+		let tree_subcommand = graph::TreeSubCommand;
 		let update_subcommand = update::SubCommand;
 		let mut subcommands = std::collections::HashMap::<_, &util::SubCommand>::new();
+		subcommands.insert("apply-plan", &apply_plan_subcommand);
+		subcommands.insert("cache", &cache_subcommand);
+		subcommands.insert("dedupe", &dedupe_subcommand);
+		subcommands.insert("dev", &dev_subcommand);
 		subcommands.insert("disable", &disable_subcommand);
 		subcommands.insert("enable", &enable_subcommand);
+		subcommands.insert("graph", &graph_subcommand);
 		subcommands.insert("install", &install_subcommand);
+		subcommands.insert("lint", &lint_subcommand);
 		subcommands.insert("list", &list_subcommand);
+		subcommands.insert("outdated", &outdated_subcommand);
+		subcommands.insert("pack", &pack_subcommand);
 		subcommands.insert("remove", &remove_subcommand);
 		subcommands.insert("search", &search_subcommand);
 		subcommands.insert("show", &show_subcommand);
+		subcommands.insert("tree", &tree_subcommand);
 		subcommands.insert("update", &update_subcommand);
 		let subcommands = subcommands;
 
@@ -88,7 +127,16 @@ quick_main!(|| -> Result<()> {
 			(@setting VersionlessSubcommands)
 			(@arg proxy: --proxy +takes_value "HTTP proxy URL")
 			(@arg yes: -y --yes "Answer yes to all prompts")
-			(@arg no: -n --no conflicts_with("yes") "Answer no to all prompts"));
+			(@arg no: -n --no conflicts_with("yes") "Answer no to all prompts")
+			(@arg wait: --wait "Wait for other instances of fac to finish instead of failing")
+			(@arg dry_run: long("dry-run") "Print the changes that would be made without making them")
+			(@arg save_plan: long("save-plan") +takes_value requires("dry_run") "Save the plan computed in dry-run mode to this file, for use with `fac apply-plan`")
+			(@arg output: --output +takes_value possible_values(&["json", "text"]) default_value("text") "Output format of list, show, search, outdated and cache list")
+			(@arg install_strategy: long("install-strategy") +takes_value possible_values(&["copy", "hardlink", "symlink", "reflink"]) default_value("copy") "How to install mods from the cache into the mods directory. Falls back to copying if the strategy isn't possible, such as across filesystems.")
+			(@arg disable_new: long("disable-new") "Leave mods that are installed for the first time disabled in mod-list.json")
+			(@arg max_downloads: long("max-downloads") +takes_value default_value("4") "Maximum number of mod releases to download at the same time")
+			(@arg retries: --retries +takes_value default_value("3") "Number of times to retry requests to the mods portal that fail with a transient error. A download that fails partway through is not retried, but is resumed from where it left off the next time.")
+			(@arg retry_delay: long("retry-delay") +takes_value default_value("1") "Seconds to wait before the first retry. The delay doubles with every retry."));
 
 		let app = subcommands.iter().fold(app, |app, (name, subcommand)|
 			app.subcommand(subcommand.build_subcommand(clap::SubCommand::with_name(name))));
@@ -111,20 +159,60 @@ quick_main!(|| -> Result<()> {
 			(true, true) => unreachable!(),
 		};
 
+		let retries = matches.value_of("retries").unwrap();
+		let retries = retries.parse().chain_err(|| format!(r#"Could not parse "{}" as a number of retries"#, retries))?;
+
+		let retry_delay = matches.value_of("retry_delay").unwrap();
+		let retry_delay = retry_delay.parse().chain_err(|| format!(r#"Could not parse "{}" as a number of seconds"#, retry_delay))?;
+
+		let retry_policy = factorio_mods_web::RetryPolicy {
+			max_retries: retries,
+			initial_delay: ::std::time::Duration::from_secs(retry_delay),
+			..Default::default()
+		};
+
+		let max_concurrent_downloads = matches.value_of("max_downloads").unwrap();
+		let max_concurrent_downloads: usize =
+			max_concurrent_downloads.parse().ok()
+			.and_then(|max_concurrent_downloads| if max_concurrent_downloads > 0 { Some(max_concurrent_downloads) } else { None })
+			.ok_or_else(|| format!(r#"Could not parse "{}" as a positive number of downloads"#, max_concurrent_downloads))?;
+
+		let global_options = util::GlobalOptions {
+			prompt_override,
+			dry_run: matches.is_present("dry_run"),
+			save_plan: matches.value_of("save_plan"),
+			output_format: match matches.value_of("output").unwrap() {
+				"json" => util::OutputFormat::Json,
+				"text" => util::OutputFormat::Text,
+				_ => unreachable!(),
+			},
+			max_concurrent_downloads,
+			install_strategy: link::InstallStrategy::parse(matches.value_of("install_strategy").unwrap()).unwrap(),
+			enable_new_mods: !matches.is_present("disable_new"),
+			wait_for_lock: matches.is_present("wait"),
+		};
+
 		let (subcommand_name, subcommand_matches) = matches.subcommand();
 		let subcommand = subcommands[subcommand_name];
 
 		let mut core = ::factorio_mods_web::tokio_core::reactor::Core::new().chain_err(|| "Could not create Tokio event loop")?;
 
 		let local_api = factorio_mods_local::API::new().chain_err(|| "Could not initialize local API");
-		let web_api = factorio_mods_web::API::new(client, core.handle()).chain_err(|| "Could not initialize web API");
+		let web_api = factorio_mods_web::API::new(client, retry_policy, core.handle()).chain_err(|| "Could not initialize web API");
 
 		let result = subcommand.run(
 			subcommand_matches.unwrap(),
 			match local_api { Ok(ref local_api) => Ok(local_api), Err(err) => Err(err), },
 			match web_api { Ok(ref web_api) => Ok(web_api), Err(err) => Err(err), },
-			prompt_override);
+			global_options);
+
+		let result = core.run(result);
+
+		if let (util::OutputFormat::Json, Err(err)) = (global_options.output_format, &result) {
+			output::print_json(&output::ErrorRecord::from(err))?;
+			::std::process::exit(1);
+		}
 
-		core.run(result)
+		result
 	}).join().unwrap()
 });
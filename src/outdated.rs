@@ -0,0 +1,146 @@
+use ::futures::{ Future, stream };
+
+pub struct SubCommand;
+
+impl ::util::SubCommand for SubCommand {
+	fn build_subcommand<'a>(&self, subcommand: ::clap::App<'a, 'a>) -> ::clap::App<'a, 'a> {
+		clap_app!(@app (subcommand)
+			(about: "List installed mods that have newer releases, without changing anything."))
+	}
+
+	fn run<'a>(
+		&'a self,
+		_: &'a ::clap::ArgMatches<'a>,
+		local_api: ::Result<&'a ::factorio_mods_local::API>,
+		web_api: ::Result<&'a ::factorio_mods_web::API>,
+		global_options: ::util::GlobalOptions<'a>,
+	) -> Box<Future<Item = (), Error = ::Error> + 'a> {
+		use ::ResultExt;
+
+		Box::new(::async_block! {
+			let local_api = local_api?;
+			let web_api = web_api?;
+
+			let config = ::config::Config::load_read_only(local_api)?;
+
+			let game_version = local_api.game_version();
+
+			// When more than one version of a mod is installed, compare against the one that the game loads.
+			let installed_mods: ::std::collections::BTreeMap<_, _> =
+				local_api.effective_mods().chain_err(|| "Could not enumerate installed mods")?
+				.into_iter()
+				.map(|(name, versions)| (name, versions.effective))
+				.collect();
+
+			let mods =
+				stream::futures_ordered(installed_mods.keys().map(|name| ::async_block! {
+					match ::await!(web_api.get(name)) {
+						Ok(mod_) => Ok((name, Some(mod_))),
+
+						Err(::factorio_mods_web::Error(::factorio_mods_web::ErrorKind::StatusCode(_, ::factorio_mods_web::reqwest::StatusCode::NotFound), _)) =>
+							Ok((name, None)),
+
+						Err(err) => Err(err).chain_err(|| format!("Could not retrieve mod {}", name)),
+					}
+				}));
+
+			let mut records = vec![];
+
+			#[async] for (name, mod_) in mods {
+				let installed_mod = &installed_mods[name];
+
+				let mod_ = match mod_ {
+					Some(mod_) => mod_,
+					None => continue, // Not on the portal, so there's nothing to compare against.
+				};
+
+				let newest_overall = mod_.releases.iter().max_by(|release1, release2| release1.version.cmp(&release2.version));
+				let newest_overall = match newest_overall {
+					Some(newest_overall) => newest_overall,
+					None => continue,
+				};
+
+				let newest_compatible =
+					mod_.releases.iter()
+					.filter(|release| release.info_json.factorio_version.0.matches(&game_version.0))
+					.max_by(|release1, release2| release1.version.cmp(&release2.version));
+
+				if newest_overall.version <= installed_mod.info.version {
+					continue;
+				}
+
+				let mut blockers = vec![];
+
+				if newest_compatible.map_or(true, |newest_compatible| newest_compatible.version < newest_overall.version) {
+					blockers.push(format!("{} requires game version {}", newest_overall.version, newest_overall.info_json.factorio_version));
+				}
+
+				if let Some(newest_compatible) = newest_compatible {
+					if newest_compatible.version > installed_mod.info.version {
+						if let Some(req) = config.mods.get(name) {
+							if !req.0.matches(&newest_compatible.version.0) {
+								blockers.push(format!("held at {}", req));
+							}
+						}
+
+						for other_mod in installed_mods.values() {
+							for dep in &other_mod.info.dependencies {
+								if &dep.name == name && !dep.version.0.matches(&newest_compatible.version.0) {
+									blockers.push(format!("{} requires {}", other_mod.info.name, dep.version));
+								}
+							}
+						}
+					}
+				}
+
+				records.push(::output::OutdatedRecord {
+					name: name.clone(),
+					installed: installed_mod.info.version.clone(),
+					compatible: newest_compatible.map(|newest_compatible| newest_compatible.version.clone()),
+					latest: newest_overall.version.clone(),
+					blocked_by: blockers,
+				});
+			}
+
+			if let ::util::OutputFormat::Json = global_options.output_format {
+				::output::print_json(&records)?;
+				return Ok(());
+			}
+
+			if records.is_empty() {
+				println!("All installed mods are up to date.");
+				return Ok(());
+			}
+
+			let mut rows = vec![[
+				"Name".to_string(),
+				"Installed".to_string(),
+				"Compatible".to_string(),
+				"Latest".to_string(),
+				"Blocked by".to_string(),
+			]];
+
+			rows.extend(records.into_iter().map(|record| [
+				record.name.to_string(),
+				record.installed.to_string(),
+				record.compatible.map_or_else(|| "-".to_string(), |compatible| compatible.to_string()),
+				record.latest.to_string(),
+				if record.blocked_by.is_empty() { "-".to_string() } else { record.blocked_by.join("; ") },
+			]));
+
+			let mut widths = [0; 4];
+			for (i, width) in widths.iter_mut().enumerate() {
+				*width = rows.iter().map(|row| row[i].len()).max().unwrap();
+			}
+
+			for row in &rows {
+				println!(
+					"{:w0$}  {:w1$}  {:w2$}  {:w3$}  {}",
+					row[0], row[1], row[2], row[3], row[4],
+					w0 = widths[0], w1 = widths[1], w2 = widths[2], w3 = widths[3]);
+			}
+
+			Ok(())
+		})
+	}
+}
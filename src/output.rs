@@ -0,0 +1,367 @@
+//! Records emitted by subcommands in JSON output mode.
+//!
+//! The field names and types of these records are a stable schema. Fields may be added in the future, but existing fields will not be
+//! renamed, removed or change type.
+
+use ::ResultExt;
+
+/// Prints the given value to stdout as JSON.
+pub fn print_json<T>(value: &T) -> ::Result<()> where T: ::serde::Serialize {
+	let stdout = ::std::io::stdout();
+	let mut stdout = stdout.lock();
+	::serde_json::to_writer_pretty(&mut stdout, value).chain_err(|| "Could not write JSON output")?;
+	::std::io::Write::write_all(&mut stdout, b"\n").chain_err(|| "Could not write JSON output")?;
+	Ok(())
+}
+
+/// An installed mod, emitted by `fac list`
+#[derive(Debug, ::serde_derive::Serialize)]
+pub struct InstalledModRecord<'a> {
+	/// The name of the mod.
+	pub name: &'a ::factorio_mods_common::ModName,
+
+	/// The installed version of the mod.
+	pub version: &'a ::factorio_mods_common::ReleaseVersion,
+
+	/// The title of the mod.
+	pub title: &'a str,
+
+	/// The authors of the mod.
+	pub author: Vec<&'a str>,
+
+	/// The description of the mod, if any.
+	pub description: Option<&'a str>,
+
+	/// The versions of the game supported by the mod, as a version requirement string.
+	pub factorio_version: &'a ::factorio_mods_common::ModVersionReq,
+
+	/// The URL of the homepage of the mod, if any.
+	pub homepage: Option<&'a str>,
+
+	/// The dependencies of the mod.
+	pub dependencies: Vec<DependencyRecord<'a>>,
+
+	/// The path of the mod in the mods directory.
+	pub path: &'a ::std::path::Path,
+
+	/// `"zipped"` or `"unpacked"`
+	pub mod_type: &'static str,
+
+	/// Whether the mod is enabled in `mod-list.json`
+	pub enabled: bool,
+
+	/// `"hardlink"` or `"symlink"` if the mod is linked to another file or directory, such as the cached release it was installed from,
+	/// otherwise `null`
+	pub link: Option<&'static str>,
+
+	/// The path that the mod links to, if it is a symbolic link.
+	pub link_target: Option<&'a ::std::path::Path>,
+
+	/// If more than one copy of the mod is installed and the game loads a different one instead of this one, the version of that copy.
+	/// Otherwise `null`
+	pub shadowed_by: Option<&'a ::factorio_mods_common::ReleaseVersion>,
+}
+
+impl<'a> InstalledModRecord<'a> {
+	pub fn new(
+		installed_mod: &'a ::factorio_mods_local::InstalledMod,
+		enabled: bool,
+		link_state: &'a ::link::LinkState,
+		shadowed_by: Option<&'a ::factorio_mods_common::ReleaseVersion>,
+	) -> Self {
+		InstalledModRecord {
+			name: &installed_mod.info.name,
+			version: &installed_mod.info.version,
+			title: &installed_mod.info.title.0,
+			author: installed_mod.info.author.iter().map(|author| &*author.0).collect(),
+			description: installed_mod.info.description.as_ref().map(|description| &*description.0),
+			factorio_version: &installed_mod.info.factorio_version,
+			homepage: installed_mod.info.homepage.as_ref().map(|homepage| &*homepage.0),
+			dependencies: installed_mod.info.dependencies.iter().map(DependencyRecord::new).collect(),
+			path: &installed_mod.path,
+			mod_type: match installed_mod.mod_type {
+				::factorio_mods_local::InstalledModType::Zipped => "zipped",
+				::factorio_mods_local::InstalledModType::Unpacked => "unpacked",
+			},
+			enabled,
+			link: link_state.name(),
+			link_target: match *link_state {
+				::link::LinkState::Symlink(ref target) => Some(target),
+				_ => None,
+			},
+			shadowed_by,
+		}
+	}
+}
+
+/// A dependency of a mod.
+#[derive(Debug, ::serde_derive::Serialize)]
+pub struct DependencyRecord<'a> {
+	/// The name of the dependency.
+	pub name: &'a ::factorio_mods_common::ModName,
+
+	/// The version requirement of the dependency, as a version requirement string.
+	pub version: &'a ::factorio_mods_common::ModVersionReq,
+
+	/// Whether the dependency is required (`true`) or optional (`false`).
+	pub required: bool,
+}
+
+impl<'a> DependencyRecord<'a> {
+	pub fn new(dependency: &'a ::factorio_mods_common::Dependency) -> Self {
+		DependencyRecord {
+			name: &dependency.name,
+			version: &dependency.version,
+			required: dependency.required,
+		}
+	}
+}
+
+/// A mod on the mods portal, emitted by `fac show`
+#[derive(Debug, ::serde_derive::Serialize)]
+pub struct ModRecord<'a> {
+	/// The name of the mod.
+	pub name: &'a ::factorio_mods_common::ModName,
+
+	/// The title of the mod.
+	pub title: &'a str,
+
+	/// The authors of the mod.
+	pub owner: Vec<&'a str>,
+
+	/// A short summary of the mod.
+	pub summary: &'a str,
+
+	/// The number of times the mod has been downloaded.
+	pub downloads_count: u64,
+
+	/// All the releases of the mod.
+	pub releases: Vec<ReleaseRecord<'a>>,
+
+	/// A longer description of the mod. Only set by `fac show --full`
+	pub description: Option<&'a str>,
+
+	/// The changelog of the mod. Only set by `fac show --full`
+	pub changelog: Option<&'a str>,
+
+	/// The category of the mod. Only set by `fac show --full`
+	pub category: Option<&'a str>,
+
+	/// The names of the tags of the mod. Only set by `fac show --full`
+	pub tags: Option<Vec<&'a str>>,
+
+	/// The URL of the homepage of the mod. Only set by `fac show --full`
+	pub homepage: Option<&'a str>,
+
+	/// The path of the mod's repository on GitHub, of the form `owner/repository`. Only set by `fac show --full`
+	pub github_path: Option<&'a str>,
+
+	/// The name of the license of the mod. Only set by `fac show --full`
+	pub license: Option<&'a str>,
+
+	/// The URL of the thumbnail of the mod, relative to the mods portal. Only set by `fac show --full`
+	pub thumbnail: Option<&'a str>,
+
+	/// The date and time at which the mod was created, as returned by the mods portal. Only set by `fac show --full`
+	pub created_at: Option<&'a str>,
+
+	/// The date and time at which the mod was last updated, as returned by the mods portal. Only set by `fac show --full`
+	pub updated_at: Option<&'a str>,
+}
+
+impl<'a> ModRecord<'a> {
+	pub fn new(mod_: &'a ::factorio_mods_web::Mod) -> Self {
+		ModRecord {
+			name: &mod_.name,
+			title: &mod_.title.0,
+			owner: mod_.owner.iter().map(|owner| &*owner.0).collect(),
+			summary: &mod_.summary.0,
+			downloads_count: mod_.downloads_count.0,
+			releases: mod_.releases.iter().map(ReleaseRecord::new).collect(),
+			description: None,
+			changelog: None,
+			category: None,
+			tags: None,
+			homepage: None,
+			github_path: None,
+			license: None,
+			thumbnail: None,
+			created_at: None,
+			updated_at: None,
+		}
+	}
+
+	pub fn new_full(mod_: &'a ::factorio_mods_web::ModFull) -> Self {
+		ModRecord {
+			name: &mod_.name,
+			title: &mod_.title.0,
+			owner: mod_.owner.iter().map(|owner| &*owner.0).collect(),
+			summary: &mod_.summary.0,
+			downloads_count: mod_.downloads_count.0,
+			releases: mod_.releases.iter().map(ReleaseRecord::new).collect(),
+			description: mod_.description.as_ref().map(|description| &*description.0),
+			changelog: mod_.changelog.as_ref().map(|changelog| &*changelog.0),
+			category: mod_.category.as_ref().map(|category| &*category.0),
+			tags: Some(mod_.tags.iter().map(|tag| &*tag.name).collect()),
+			homepage: mod_.homepage.as_ref().map(|homepage| &*homepage.0),
+			github_path: mod_.github_path.as_ref().map(|github_path| &*github_path.0),
+			license: mod_.license.as_ref().map(|license| &*license.name),
+			thumbnail: mod_.thumbnail.as_ref().map(|thumbnail| &*thumbnail.0),
+			created_at: Some(&mod_.created_at.0),
+			updated_at: mod_.updated_at.as_ref().map(|updated_at| &*updated_at.0),
+		}
+	}
+}
+
+/// A mod in the search results of the mods portal, emitted by `fac search`
+#[derive(Debug, ::serde_derive::Serialize)]
+pub struct SearchResultRecord<'a> {
+	/// The name of the mod.
+	pub name: &'a ::factorio_mods_common::ModName,
+
+	/// The title of the mod.
+	pub title: &'a str,
+
+	/// The authors of the mod.
+	pub owner: Vec<&'a str>,
+
+	/// A short summary of the mod.
+	pub summary: &'a str,
+
+	/// The number of times the mod has been downloaded.
+	pub downloads_count: u64,
+
+	/// The latest release of the mod.
+	pub latest_release: ReleaseRecord<'a>,
+}
+
+impl<'a> SearchResultRecord<'a> {
+	pub fn new(mod_: &'a ::factorio_mods_web::SearchResponseMod) -> Self {
+		SearchResultRecord {
+			name: &mod_.name,
+			title: &mod_.title.0,
+			owner: mod_.owner.iter().map(|owner| &*owner.0).collect(),
+			summary: &mod_.summary.0,
+			downloads_count: mod_.downloads_count.0,
+			latest_release: ReleaseRecord::new(&mod_.latest_release),
+		}
+	}
+}
+
+impl<'a> From<&'a ::search_index::IndexedMod> for SearchResultRecord<'a> {
+	fn from(mod_: &'a ::search_index::IndexedMod) -> Self {
+		SearchResultRecord {
+			name: &mod_.name,
+			title: &mod_.title,
+			owner: mod_.owner.iter().map(String::as_str).collect(),
+			summary: &mod_.summary,
+			downloads_count: mod_.downloads_count,
+			latest_release: ReleaseRecord {
+				version: &mod_.latest_release.version,
+				factorio_version: &mod_.latest_release.factorio_version,
+				file_name: &mod_.latest_release.file_name,
+				released_at: &mod_.latest_release.released_at,
+				sha1: &mod_.latest_release.sha1,
+				dependencies: None,
+			},
+		}
+	}
+}
+
+/// A release of a mod on the mods portal.
+#[derive(Debug, ::serde_derive::Serialize)]
+pub struct ReleaseRecord<'a> {
+	/// The version of the release.
+	pub version: &'a ::factorio_mods_common::ReleaseVersion,
+
+	/// The versions of the game supported by the release, as a version requirement string.
+	pub factorio_version: &'a ::factorio_mods_common::ModVersionReq,
+
+	/// The filename of the release.
+	pub file_name: &'a str,
+
+	/// The date and time at which the release was created, as returned by the mods portal.
+	pub released_at: &'a str,
+
+	/// The SHA-1 hash of the release file, as a hex string.
+	pub sha1: &'a str,
+
+	/// The dependencies of the release, or `null` if the mods portal did not return them.
+	pub dependencies: Option<Vec<DependencyRecord<'a>>>,
+}
+
+impl<'a> ReleaseRecord<'a> {
+	pub fn new(release: &'a ::factorio_mods_web::ModRelease) -> Self {
+		ReleaseRecord {
+			version: &release.version,
+			factorio_version: &release.info_json.factorio_version,
+			file_name: &release.filename.0,
+			released_at: &release.released_at.0,
+			sha1: &release.sha1.0,
+			dependencies: release.info_json.dependencies.as_ref().map(|dependencies| dependencies.iter().map(DependencyRecord::new).collect()),
+		}
+	}
+}
+
+/// An installed mod that has newer releases, emitted by `fac outdated`
+#[derive(Debug, ::serde_derive::Serialize)]
+pub struct OutdatedRecord {
+	/// The name of the mod.
+	pub name: ::factorio_mods_common::ModName,
+
+	/// The installed version of the mod.
+	pub installed: ::factorio_mods_common::ReleaseVersion,
+
+	/// The newest release that is compatible with the current game version, or `null` if there is none.
+	pub compatible: Option<::factorio_mods_common::ReleaseVersion>,
+
+	/// The newest release.
+	pub latest: ::factorio_mods_common::ReleaseVersion,
+
+	/// Human-readable reasons why the mod can't be upgraded to the newest release. Empty if nothing blocks the upgrade.
+	pub blocked_by: Vec<String>,
+}
+
+/// A file in the cache directory, emitted by `fac cache list`
+#[derive(Debug, ::serde_derive::Serialize)]
+pub struct CachedReleaseRecord<'a> {
+	/// The path of the file.
+	pub path: &'a ::std::path::Path,
+
+	/// The size of the file in bytes.
+	pub size: u64,
+
+	/// The name of the mod, or `null` if the file is not a valid mod release.
+	pub name: Option<&'a ::factorio_mods_common::ModName>,
+
+	/// The version of the mod release, or `null` if the file is not a valid mod release.
+	pub version: Option<&'a ::factorio_mods_common::ReleaseVersion>,
+
+	/// Whether the file is a partial download.
+	pub partial: bool,
+
+	/// Whether this is an unpacked mod that was moved into the cache by `fac dedupe`, rather than a zip.
+	pub unpacked: bool,
+
+	/// Whether this release of the mod is installed.
+	pub installed: bool,
+}
+
+/// An error, emitted by any subcommand that fails.
+#[derive(Debug, ::serde_derive::Serialize)]
+pub struct ErrorRecord {
+	/// The error message.
+	pub error: String,
+
+	/// The messages of the errors that caused this error, outermost first.
+	pub causes: Vec<String>,
+}
+
+impl<'a> From<&'a ::Error> for ErrorRecord {
+	fn from(err: &'a ::Error) -> Self {
+		ErrorRecord {
+			error: err.to_string(),
+			causes: err.iter().skip(1).map(ToString::to_string).collect(),
+		}
+	}
+}
@@ -0,0 +1,355 @@
+use ::futures::{ Future, IntoFuture };
+
+use ::ResultExt;
+
+/// The file in a mod's directory that lists patterns of files to leave out of its zip, one per line.
+const IGNORE_FILE_NAME: &str = ".facignore";
+
+/// Patterns of files that are always left out of a mod's zip, in addition to those in its ignore file.
+///
+/// This includes zips being written by an earlier run that was interrupted. Zips of earlier versions of the mod are also left out, see `Ignore::load`
+const DEFAULT_IGNORE_PATTERNS: &[&str] = &[".git", ".gitattributes", ".gitignore", ".gitmodules", ".hg", ".svn", ".DS_Store", "Thumbs.db", "*.new", IGNORE_FILE_NAME];
+
+pub struct SubCommand;
+
+impl ::util::SubCommand for SubCommand {
+	fn build_subcommand<'a>(&self, subcommand: ::clap::App<'a, 'a>) -> ::clap::App<'a, 'a> {
+		clap_app!(@app (subcommand)
+			(about: "Build the release zip of a mod from its directory.\n\n\
+			         The zip is named name_version.zip after the name and version in the mod's info.json, and contains the mod in a single \
+			         name_version directory. Files that match a pattern in the mod's .facignore file are left out, as are version control files. \
+			         Patterns without a / match files and directories with that name anywhere in the mod, and patterns with a / match paths \
+			         relative to the mod's directory. Zips of the mod named name_*.zip and partially written *.new files are always left out.\n\n\
+			         Entries are sorted and have fixed timestamps, so packing the same files always produces an identical zip.")
+			(@arg out_dir: long("out-dir") +takes_value "directory to write the zip to, defaults to the directory that contains the mod's directory")
+			(@arg path: +required "path of the directory that contains the mod's info.json"))
+	}
+
+	fn run<'a>(
+		&'a self,
+		matches: &'a ::clap::ArgMatches<'a>,
+		_: ::Result<&'a ::factorio_mods_local::API>,
+		_: ::Result<&'a ::factorio_mods_web::API>,
+		_: ::util::GlobalOptions<'a>,
+	) -> Box<Future<Item = (), Error = ::Error> + 'a> {
+		#[cfg_attr(feature = "cargo-clippy", allow(unit_arg))]
+		let result: ::Result<_> = do catch {
+			let directory = ::std::path::Path::new(matches.value_of("path").unwrap());
+
+			ensure!(directory.is_dir(), "{} is not a directory", directory.display());
+
+			// Paths like `.` don't have a parent of their own, so the mod's directory is canonicalized to find it.
+			let out_directory = match matches.value_of("out_dir") {
+				Some(out_directory) => ::std::path::PathBuf::from(out_directory),
+				None => {
+					let directory = directory.canonicalize().chain_err(|| format!("Could not canonicalize {}", directory.display()))?;
+					directory.parent().ok_or_else(|| format!("{} does not have a parent directory to write the zip to", directory.display()))?.to_path_buf()
+				},
+			};
+			let out_directory = &*out_directory;
+
+			let mod_ =
+				::factorio_mods_local::InstalledMod::parse(directory.to_path_buf())
+				.chain_err(|| format!("Could not parse the mod at {}", directory.display()))?;
+			let name = &mod_.info.name;
+			let version = &mod_.info.version;
+
+			ensure!(
+				::util::is_valid_mod_name(&name.0),
+				r#"The mod name "{}" is not valid. Names can only contain up to 100 letters, digits, dashes and underscores."#,
+				name);
+
+			let toplevel = format!("{}_{}", name, version);
+
+			::std::fs::create_dir_all(out_directory).chain_err(|| format!("Could not create directory {}", out_directory.display()))?;
+			let zip_path = out_directory.join(format!("{}.zip", toplevel));
+
+			let ignore = Ignore::load(directory, name)?;
+
+			// The zip must not contain itself if it's written inside the mod's directory.
+			let zip_path_canonicalized = out_directory.canonicalize().chain_err(|| format!("Could not canonicalize {}", out_directory.display()))?.join(format!("{}.zip", toplevel));
+
+			let mut entries = vec![];
+			collect_entries(directory, "", &ignore, &zip_path_canonicalized, &mut vec![], &mut entries)?;
+			entries.sort_by(|entry1, entry2| entry1.name.cmp(&entry2.name));
+
+			println!("Packing {} {} into {} ...", name, version, zip_path.display());
+
+			let mut partial_zip_path = zip_path.clone().into_os_string();
+			partial_zip_path.push(".new");
+			let partial_zip_path: ::std::path::PathBuf = partial_zip_path.into();
+
+			let write_result = write_zip(&partial_zip_path, &toplevel, &entries);
+			if write_result.is_err() {
+				let _ = ::std::fs::remove_file(&partial_zip_path);
+			}
+			write_result?;
+
+			::std::fs::rename(&partial_zip_path, &zip_path).chain_err(|| format!("Could not rename {} to {}", partial_zip_path.display(), zip_path.display()))?;
+
+			println!("Packing {} {} into {} ... done", name, version, zip_path.display());
+			println!("{} files", entries.iter().filter(|entry| !entry.is_directory).count());
+			println!("SHA1: {}", ::download::sha1(&zip_path)?);
+		};
+
+		Box::new(result.into_future())
+	}
+}
+
+/// A file or directory that will be added to the zip.
+#[derive(Debug)]
+struct Entry {
+	/// The path of the entry relative to the mod's directory, with `/` separators. Directories have a trailing `/`
+	name: String,
+
+	path: ::std::path::PathBuf,
+
+	is_directory: bool,
+}
+
+/// The patterns of files to leave out of the zip.
+struct Ignore {
+	/// Patterns without a `/`, which are matched against the names of files and directories.
+	names: ::globset::GlobSet,
+
+	/// Patterns with a `/`, which are matched against paths relative to the mod's directory.
+	paths: ::globset::GlobSet,
+}
+
+impl Ignore {
+	/// Loads the default patterns and the patterns in the ignore file of the given mod directory, if it has one.
+	/// Zips of the mod with the given name in the top of its directory, such as those packed with `--out-dir .`, are also ignored.
+	///
+	/// Empty lines and lines starting with # are ignored.
+	fn load(directory: &::std::path::Path, name: &::factorio_mods_common::ModName) -> ::Result<Self> {
+		let ignore_file_path = directory.join(IGNORE_FILE_NAME);
+
+		let contents = match ::std::fs::read_to_string(&ignore_file_path) {
+			Ok(contents) => contents,
+			Err(ref err) if err.kind() == ::std::io::ErrorKind::NotFound => String::new(),
+			Err(err) => return Err(err).chain_err(|| format!("Could not read {}", ignore_file_path.display())),
+		};
+
+		// Valid mod names don't contain any characters that are special in patterns.
+		let zips_pattern = format!("/{}_*.zip", name);
+
+		let patterns =
+			DEFAULT_IGNORE_PATTERNS.iter().cloned()
+			.chain(::std::iter::once(&zips_pattern[..]))
+			.chain(contents.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')));
+
+		let mut names = ::globset::GlobSetBuilder::new();
+		let mut paths = ::globset::GlobSetBuilder::new();
+
+		for pattern in patterns {
+			// A trailing / is allowed to mark directories, but is not needed.
+			let pattern = pattern.trim_right_matches('/');
+
+			let glob =
+				::globset::GlobBuilder::new(pattern.trim_left_matches('/'))
+				.literal_separator(true)
+				.build()
+				.chain_err(|| format!(r#"Could not parse pattern "{}" in {}"#, pattern, ignore_file_path.display()))?;
+
+			if pattern.contains('/') {
+				paths.add(glob);
+			}
+			else {
+				names.add(glob);
+			}
+		}
+
+		Ok(Ignore {
+			names: names.build().chain_err(|| format!("Could not parse patterns in {}", ignore_file_path.display()))?,
+			paths: paths.build().chain_err(|| format!("Could not parse patterns in {}", ignore_file_path.display()))?,
+		})
+	}
+
+	fn is_ignored(&self, file_name: &str, relative_path: &str) -> bool {
+		self.names.is_match(file_name) || self.paths.is_match(relative_path)
+	}
+}
+
+/// Adds the files and directories under the given directory that aren't ignored to `entries`. The contents of ignored directories are not visited.
+///
+/// `ancestors` holds the canonical paths of the directories being visited, so that a symbolic link to one of them is reported instead of followed forever.
+fn collect_entries(
+	directory: &::std::path::Path,
+	prefix: &str,
+	ignore: &Ignore,
+	zip_path: &::std::path::Path,
+	ancestors: &mut Vec<::std::path::PathBuf>,
+	entries: &mut Vec<Entry>,
+) -> ::Result<()> {
+	let directory_canonicalized = directory.canonicalize().chain_err(|| format!("Could not canonicalize {}", directory.display()))?;
+	ensure!(!ancestors.contains(&directory_canonicalized), "{} is a symbolic link to a directory that contains it", directory.display());
+	ancestors.push(directory_canonicalized);
+
+	for directory_entry in ::std::fs::read_dir(directory).chain_err(|| format!("Could not read directory {}", directory.display()))? {
+		let directory_entry = directory_entry.chain_err(|| format!("Could not read directory {}", directory.display()))?;
+		let path = directory_entry.path();
+
+		let file_name = directory_entry.file_name();
+		let file_name = file_name.to_str().ok_or_else(|| format!("The file name of {} is not valid UTF-8", path.display()))?;
+
+		let relative_path = format!("{}{}", prefix, file_name);
+		if ignore.is_ignored(file_name, &relative_path) {
+			continue;
+		}
+
+		// Symbolic links are followed, so that the zip contains what they point to.
+		let metadata = ::std::fs::metadata(&path).chain_err(|| format!("Could not read metadata of {}", path.display()))?;
+
+		if metadata.is_dir() {
+			let name = format!("{}/", relative_path);
+			collect_entries(&path, &name, ignore, zip_path, ancestors, entries)?;
+			entries.push(Entry { name, path, is_directory: true });
+		}
+		else if path.canonicalize().ok().as_ref().map(|path| &**path) != Some(zip_path) {
+			entries.push(Entry { name: relative_path, path, is_directory: false });
+		}
+	}
+
+	ancestors.pop();
+
+	Ok(())
+}
+
+/// Writes the given entries into a zip at the given path, inside a single top-level directory with the given name.
+///
+/// The entries must already be sorted. All entries get the same timestamp and permissions, so that the zip only depends on the names and contents of the files.
+fn write_zip(zip_path: &::std::path::Path, toplevel: &str, entries: &[Entry]) -> ::Result<()> {
+	// The earliest time that a zip file can represent.
+	let timestamp = ::time::Tm { tm_year: 80, tm_mon: 0, tm_mday: 1, ..::time::empty_tm() };
+
+	let file_options =
+		::zip::write::FileOptions::default()
+		.compression_method(::zip::CompressionMethod::Deflated)
+		.last_modified_time(timestamp)
+		.unix_permissions(0o644);
+
+	let directory_options =
+		::zip::write::FileOptions::default()
+		.last_modified_time(timestamp)
+		.unix_permissions(0o755);
+
+	let file = ::std::fs::File::create(zip_path).chain_err(|| format!("Could not create file {}", zip_path.display()))?;
+	let mut writer = ::zip::ZipWriter::new(::std::io::BufWriter::new(file));
+
+	writer.add_directory(format!("{}/", toplevel), directory_options).chain_err(|| format!("Could not write to {}", zip_path.display()))?;
+
+	for entry in entries {
+		let name = format!("{}/{}", toplevel, entry.name);
+
+		if entry.is_directory {
+			writer.add_directory(name, directory_options).chain_err(|| format!("Could not write to {}", zip_path.display()))?;
+		}
+		else {
+			writer.start_file(name, file_options).chain_err(|| format!("Could not write to {}", zip_path.display()))?;
+
+			let mut file = ::std::fs::File::open(&entry.path).chain_err(|| format!("Could not open {} for reading", entry.path.display()))?;
+			let _ = ::std::io::copy(&mut file, &mut writer).chain_err(|| format!("Could not add {} to {}", entry.path.display(), zip_path.display()))?;
+		}
+	}
+
+	let mut writer = writer.finish().chain_err(|| format!("Could not write to {}", zip_path.display()))?;
+	::std::io::Write::flush(&mut writer).chain_err(|| format!("Could not write to {}", zip_path.display()))?;
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn temp_directory(name: &str) -> ::std::path::PathBuf {
+		let directory = ::std::env::temp_dir().join(format!("fac-test-{}-{}", ::std::process::id(), name));
+		let _ = ::std::fs::remove_dir_all(&directory);
+		::std::fs::create_dir_all(&directory).unwrap();
+		directory
+	}
+
+	#[test]
+	fn ignore() {
+		let directory = temp_directory("ignore");
+		::std::fs::write(directory.join(IGNORE_FILE_NAME), "# comment\n\n*.psd\n/build/\ngraphics/raw\n").unwrap();
+
+		let ignore = Ignore::load(&directory, &::factorio_mods_common::ModName("foo".to_string())).unwrap();
+
+		assert!(ignore.is_ignored(".git", ".git"));
+		assert!(ignore.is_ignored(".git", "locale/.git"));
+		assert!(ignore.is_ignored(IGNORE_FILE_NAME, IGNORE_FILE_NAME));
+		assert!(ignore.is_ignored("foo_1.0.0.zip.new", "foo_1.0.0.zip.new"));
+		assert!(ignore.is_ignored("foo_1.0.0.zip", "foo_1.0.0.zip"));
+		assert!(ignore.is_ignored("icon.psd", "graphics/icon.psd"));
+		assert!(ignore.is_ignored("build", "build"));
+		assert!(ignore.is_ignored("raw", "graphics/raw"));
+
+		assert!(!ignore.is_ignored("foo_1.0.0.zip", "data/foo_1.0.0.zip"));
+		assert!(!ignore.is_ignored("bar_1.0.0.zip", "bar_1.0.0.zip"));
+		assert!(!ignore.is_ignored("build", "data/build"));
+		assert!(!ignore.is_ignored("raw", "raw"));
+		assert!(!ignore.is_ignored("info.json", "info.json"));
+
+		::std::fs::remove_dir_all(&directory).unwrap();
+	}
+
+	#[test]
+	fn write_zip_is_deterministic() {
+		let directory = temp_directory("write_zip_is_deterministic");
+		let mod_directory = directory.join("mod");
+		::std::fs::create_dir_all(mod_directory.join("graphics")).unwrap();
+		::std::fs::write(mod_directory.join("info.json"), r#"{ "name": "foo", "version": "1.0.0" }"#).unwrap();
+		::std::fs::write(mod_directory.join("data.lua"), "").unwrap();
+		::std::fs::write(mod_directory.join("graphics").join("icon.png"), [0u8, 1, 2, 3]).unwrap();
+		::std::fs::write(mod_directory.join("foo_0.9.0.zip"), "old release").unwrap();
+
+		let ignore = Ignore::load(&mod_directory, &::factorio_mods_common::ModName("foo".to_string())).unwrap();
+
+		let pack = |zip_path: &::std::path::Path| {
+			let mut entries = vec![];
+			collect_entries(&mod_directory, "", &ignore, zip_path, &mut vec![], &mut entries).unwrap();
+			entries.sort_by(|entry1, entry2| entry1.name.cmp(&entry2.name));
+			write_zip(zip_path, "foo_1.0.0", &entries).unwrap();
+			entries.into_iter().map(|entry| entry.name).collect::<Vec<_>>()
+		};
+
+		let zip_path1 = directory.join("1.zip");
+		let names = pack(&zip_path1);
+		assert_eq!(names, ["data.lua", "graphics/", "graphics/icon.png", "info.json"]);
+
+		// The modification times of the files change, but the zip must not.
+		::std::fs::write(mod_directory.join("data.lua"), "").unwrap();
+
+		let zip_path2 = directory.join("2.zip");
+		let _ = pack(&zip_path2);
+
+		assert_eq!(::std::fs::read(&zip_path1).unwrap(), ::std::fs::read(&zip_path2).unwrap());
+
+		::std::fs::remove_dir_all(&directory).unwrap();
+	}
+	#[cfg(unix)]
+	#[test]
+	fn collect_entries_detects_symlink_cycles() {
+		let directory = temp_directory("collect_entries_detects_symlink_cycles");
+		let mod_directory = directory.join("mod");
+		::std::fs::create_dir_all(mod_directory.join("graphics")).unwrap();
+		::std::fs::write(mod_directory.join("info.json"), r#"{ "name": "foo", "version": "1.0.0" }"#).unwrap();
+
+		let ignore = Ignore::load(&mod_directory, &::factorio_mods_common::ModName("foo".to_string())).unwrap();
+		let zip_path = directory.join("foo_1.0.0.zip");
+
+		// A symbolic link to a sibling directory is followed.
+		::std::os::unix::fs::symlink(mod_directory.join("graphics"), mod_directory.join("icons")).unwrap();
+		let mut entries = vec![];
+		collect_entries(&mod_directory, "", &ignore, &zip_path, &mut vec![], &mut entries).unwrap();
+		assert!(entries.iter().any(|entry| entry.name == "icons/"));
+
+		// A symbolic link to a directory that contains it is an error.
+		::std::os::unix::fs::symlink(&mod_directory, mod_directory.join("graphics").join("mod")).unwrap();
+		let mut entries = vec![];
+		assert!(collect_entries(&mod_directory, "", &ignore, &zip_path, &mut vec![], &mut entries).is_err());
+
+		::std::fs::remove_dir_all(&directory).unwrap();
+	}
+}
@@ -0,0 +1,504 @@
+use ::ResultExt;
+
+/// A set of changes to the mods directory, computed from a solution.
+///
+/// A plan can be saved and applied later, as long as the mods directory hasn't changed in the meantime.
+#[derive(Debug, ::serde_derive::Deserialize, ::serde_derive::Serialize)]
+pub struct Plan {
+	/// The mods directory that the plan applies to.
+	pub mods_directory: ::std::path::PathBuf,
+
+	/// The contents of the mods directory at the time the plan was computed.
+	pub mods_directory_snapshot: Vec<DirectoryEntry>,
+
+	/// The requirements that are saved to the config once the plan has been applied.
+	pub reqs: ::std::collections::BTreeMap<::factorio_mods_common::ModName, ::factorio_mods_common::ModVersionReq>,
+
+	/// Mods that will be replaced by a different version. The old and new versions are also present in `removals` and `installs` respectively.
+	pub upgrades: Vec<Upgrade>,
+
+	/// Installed mods that will be removed.
+	pub removals: Vec<Removal>,
+
+	/// Cached mods that will be installed.
+	pub installs: Vec<Install>,
+}
+
+/// A mod that will be replaced by a different version.
+#[derive(Debug, ::serde_derive::Deserialize, ::serde_derive::Serialize)]
+pub struct Upgrade {
+	pub name: ::factorio_mods_common::ModName,
+	pub from: ::factorio_mods_common::ReleaseVersion,
+	pub to: ::factorio_mods_common::ReleaseVersion,
+}
+
+/// An installed mod that will be removed.
+#[derive(Debug, ::serde_derive::Deserialize, ::serde_derive::Serialize)]
+pub struct Removal {
+	pub name: ::factorio_mods_common::ModName,
+	pub version: ::factorio_mods_common::ReleaseVersion,
+	pub path: ::std::path::PathBuf,
+	pub mod_type: ::factorio_mods_local::InstalledModType,
+}
+
+/// A cached mod that will be installed.
+#[derive(Debug, ::serde_derive::Deserialize, ::serde_derive::Serialize)]
+pub struct Install {
+	pub name: ::factorio_mods_common::ModName,
+	pub version: ::factorio_mods_common::ReleaseVersion,
+
+	/// The path of the mod in the cache.
+	pub source: ::std::path::PathBuf,
+
+	/// The path that the mod will be installed to.
+	pub target: ::std::path::PathBuf,
+
+	/// The SHA-1 hash of the release on the mods portal, as a lowercase hex string. The cached mod must still match it when the plan is applied.
+	pub sha1: String,
+}
+
+/// A mod release that a solution wants to be installed.
+#[derive(Debug)]
+pub struct SolvedMod {
+	pub version: ::factorio_mods_common::ReleaseVersion,
+
+	/// The path of the release in the cache. The release need not have been downloaded yet.
+	///
+	/// For a mod that is already installed, this is the path of the installed mod instead.
+	pub source: ::std::path::PathBuf,
+
+	/// The SHA-1 hash of the release on the mods portal, as a lowercase hex string. `None` for a mod that is already installed.
+	pub sha1: Option<String>,
+}
+
+/// An entry in the mods directory.
+#[derive(Debug, Eq, Ord, PartialEq, PartialOrd, ::serde_derive::Deserialize, ::serde_derive::Serialize)]
+pub struct DirectoryEntry {
+	pub file_name: String,
+	pub len: u64,
+
+	/// Seconds since the UNIX epoch.
+	pub modified: Option<u64>,
+}
+
+impl Plan {
+	/// Computes which old mods to uninstall and which new mods to install so that the installed mods match the given solution.
+	pub fn new(
+		mut solution: ::std::collections::HashMap<::factorio_mods_common::ModName, SolvedMod>,
+		local_api: &::factorio_mods_local::API,
+		reqs: &::std::collections::HashMap<::factorio_mods_common::ModName, ::factorio_mods_common::ModVersionReq>,
+	) -> ::Result<Self> {
+		let mods_directory = local_api.mods_directory();
+
+		let mods_directory_snapshot = snapshot(mods_directory)?;
+
+		let all_installed_mods: ::Result<::multimap::MultiMap<_, _>> =
+			local_api.installed_mods().chain_err(|| "Could not enumerate installed mods")?
+			.map(|mod_|
+				mod_
+				.map(|mod_| (mod_.info.name.clone(), mod_))
+				.chain_err(|| "Could not process an installed mod"))
+			.collect();
+
+		let all_installed_mods = all_installed_mods.chain_err(|| "Could not enumerate installed mods")?;
+
+		let mut to_uninstall = vec![];
+		let mut to_install = ::std::collections::HashMap::new();
+
+		for (name, installed_mods) in all_installed_mods {
+			match solution.remove(&name) {
+				Some(solved_mod) => {
+					let mut already_installed = false;
+
+					for installed_mod in installed_mods {
+						if solved_mod.version == installed_mod.info.version {
+							already_installed = true;
+						}
+						else {
+							to_uninstall.push(installed_mod);
+						}
+					}
+
+					if !already_installed {
+						to_install.insert(name, solved_mod);
+					}
+				},
+
+				None =>
+					to_uninstall.extend(installed_mods),
+			}
+		}
+
+		to_install.extend(solution);
+
+		let upgrades =
+			::itertools::Itertools::sorted_by(
+				to_uninstall.iter().filter_map(|installed_mod|
+					to_install.get(&installed_mod.info.name)
+					.map(|solved_mod| (installed_mod, solved_mod))),
+				|&(installed_mod1, solved_mod1), &(installed_mod2, solved_mod2)|
+					installed_mod1.info.name.cmp(&installed_mod2.info.name)
+					.then_with(|| installed_mod1.info.version.cmp(&installed_mod2.info.version))
+					.then_with(|| solved_mod1.version.cmp(&solved_mod2.version)))
+			.into_iter()
+			.map(|(installed_mod, solved_mod)| Upgrade {
+				name: installed_mod.info.name.clone(),
+				from: installed_mod.info.version.clone(),
+				to: solved_mod.version.clone(),
+			})
+			.collect();
+
+		to_uninstall.sort_by(|installed_mod1, installed_mod2|
+			installed_mod1.info.name.cmp(&installed_mod2.info.name)
+			.then_with(|| installed_mod1.info.version.cmp(&installed_mod2.info.version)));
+
+		let removals =
+			to_uninstall.into_iter()
+			.map(|installed_mod| Removal {
+				name: installed_mod.info.name,
+				version: installed_mod.info.version,
+				path: installed_mod.path,
+				mod_type: installed_mod.mod_type,
+			})
+			.collect();
+
+		let installs = installs(mods_directory, to_install)?;
+
+		Ok(Plan {
+			mods_directory: mods_directory.to_path_buf(),
+			mods_directory_snapshot,
+			reqs: reqs.iter().map(|(name, req)| (name.clone(), req.clone())).collect(),
+			upgrades,
+			removals,
+			installs,
+		})
+	}
+
+	/// Computes which mods to install so that the mods of the given solution are installed, without removing any installed mods.
+	///
+	/// Mods of the solution that are already installed at the solved version are left alone.
+	pub fn new_installs_only(
+		solution: ::std::collections::HashMap<::factorio_mods_common::ModName, SolvedMod>,
+		local_api: &::factorio_mods_local::API,
+		reqs: &::std::collections::HashMap<::factorio_mods_common::ModName, ::factorio_mods_common::ModVersionReq>,
+	) -> ::Result<Self> {
+		let mods_directory = local_api.mods_directory();
+
+		let mods_directory_snapshot = snapshot(mods_directory)?;
+
+		let mut installed_versions = ::std::collections::HashSet::new();
+		for installed_mod in local_api.installed_mods().chain_err(|| "Could not enumerate installed mods")? {
+			let installed_mod = installed_mod.chain_err(|| "Could not process an installed mod")?;
+			installed_versions.insert((installed_mod.info.name, installed_mod.info.version));
+		}
+
+		let to_install =
+			solution.into_iter()
+			.filter(|(name, solved_mod)| !installed_versions.contains(&(name.clone(), solved_mod.version.clone())))
+			.collect();
+
+		let installs = installs(mods_directory, to_install)?;
+
+		Ok(Plan {
+			mods_directory: mods_directory.to_path_buf(),
+			mods_directory_snapshot,
+			reqs: reqs.iter().map(|(name, req)| (name.clone(), req.clone())).collect(),
+			upgrades: vec![],
+			removals: vec![],
+			installs,
+		})
+	}
+
+	/// Loads a plan that was previously saved with `Plan::save`
+	pub fn load(path: &::std::path::Path) -> ::Result<Self> {
+		let file = ::std::fs::File::open(path).chain_err(|| format!("Could not open plan file {}", path.display()))?;
+		Ok(::serde_json::from_reader(file).chain_err(|| format!("Could not parse plan file {}", path.display()))?)
+	}
+
+	/// Saves this plan to the given file.
+	pub fn save(&self, path: &::std::path::Path) -> ::Result<()> {
+		::factorio_mods_local::write_json_atomic(path, ::factorio_mods_local::Backup::No, self)
+		.chain_err(|| format!("Could not write to plan file {}", path.display()))?;
+		Ok(())
+	}
+
+	/// Returns true if applying this plan wouldn't change anything.
+	pub fn is_empty(&self) -> bool {
+		self.removals.is_empty() && self.installs.is_empty()
+	}
+
+	/// Prints the changes in this plan.
+	pub fn print(&self) {
+		if !self.upgrades.is_empty() {
+			println!();
+			println!("The following mods will be upgraded:");
+			for upgrade in &self.upgrades {
+				println!("    {} {} -> {}", upgrade.name, upgrade.from, upgrade.to);
+			}
+		}
+
+		if !self.removals.is_empty() {
+			println!();
+			println!("The following mods will be removed:");
+			for removal in &self.removals {
+				println!("    {} {}", removal.name, removal.version);
+			}
+		}
+
+		if !self.installs.is_empty() {
+			println!();
+			println!("The following new mods will be installed:");
+			for install in &self.installs {
+				println!("    {} {}", install.name, install.version);
+			}
+		}
+
+		println!();
+
+		if self.is_empty() {
+			println!("Nothing to do.");
+		}
+	}
+
+	/// Ensures that this plan can still be applied to the mods directory of the given local API.
+	///
+	/// A saved plan can be edited before it's applied, so this also ensures that it only removes and installs mods directly inside
+	/// the mods directory, and only installs mods from the cache that still match the hashes of their releases.
+	pub fn check(&self, local_api: &::factorio_mods_local::API) -> ::Result<()> {
+		let mods_directory = local_api.mods_directory();
+
+		ensure!(
+			mods_directory == self.mods_directory,
+			"The plan was made for the mods directory {} but the current mods directory is {}",
+			self.mods_directory.display(), mods_directory.display());
+
+		ensure!(
+			snapshot(mods_directory)? == self.mods_directory_snapshot,
+			"The mods directory {} has changed since the plan was made. Make a new plan.",
+			mods_directory.display());
+
+		self.check_paths(mods_directory, &::config::cache_directory()?)
+	}
+
+	/// Ensures that every path in this plan is where it's expected to be, and that every cached mod matches its hash.
+	fn check_paths(&self, mods_directory: &::std::path::Path, cache_directory: &::std::path::Path) -> ::Result<()> {
+		for removal in &self.removals {
+			ensure!(
+				is_mod_path(&removal.path, mods_directory),
+				"The plan removes {} which is not a mod in the mods directory {}",
+				removal.path.display(), mods_directory.display());
+		}
+
+		for install in &self.installs {
+			ensure!(
+				is_mod_path(&install.target, mods_directory),
+				"The plan installs to {} which is not a mod in the mods directory {}",
+				install.target.display(), mods_directory.display());
+
+			ensure!(
+				install.source.parent() == Some(cache_directory),
+				"The plan installs {} which is not in the cache directory {}",
+				install.source.display(), cache_directory.display());
+
+			ensure!(install.source.is_file(), "The cached mod {} no longer exists. Make a new plan.", install.source.display());
+
+			let sha1 = ::download::sha1(&install.source)?;
+			ensure!(
+				sha1 == install.sha1,
+				"The cached mod {} has SHA-1 hash {} but the release has SHA-1 hash {}. Make a new plan.",
+				install.source.display(), sha1, install.sha1);
+		}
+
+		Ok(())
+	}
+
+	/// Applies this plan, and updates `mod-list.json` to match.
+	pub fn apply(&self, local_api: &::factorio_mods_local::API, global_options: ::util::GlobalOptions) -> ::Result<()> {
+		let install_strategy = global_options.install_strategy;
+
+		for removal in &self.removals {
+			println!(
+				"    Removing {} {} ... removing {} ...",
+				removal.name, removal.version,
+				removal.path.display());
+
+			::link::remove(&removal.path)?;
+
+			println!(
+				"    Removing {} {} ... done",
+				removal.name, removal.version);
+		}
+
+		for install in &self.installs {
+			println!("    Installing {} {} ... {} to {}", install.name, install.version, install_strategy.description(), install.target.display());
+
+			match ::link::install(&install.source, &install.target, install_strategy)? {
+				None => println!("    Installing {} {} ... done", install.name, install.version),
+				Some(err) => println!("    Installing {} {} ... done (copied instead because {} failed: {})", install.name, install.version, install_strategy.description(), err),
+			}
+		}
+
+		if !self.is_empty() {
+			println!("    Updating mod-list.json ...");
+
+			// A mod keeps its entry as long as any version of it is still installed, whether it was just installed or was already installed
+			// and only had other versions removed. If its entry pins a version, the pin is moved to the version that the game will now load.
+			let remaining_mods = local_api.effective_mods().chain_err(|| "Could not enumerate installed mods")?;
+
+			let changed_names: ::std::collections::HashSet<_> =
+				self.installs.iter().map(|install| &install.name)
+				.chain(self.removals.iter().map(|removal| &removal.name))
+				.collect();
+
+			local_api.update_mod_list(
+				remaining_mods.iter()
+				.filter(|&(name, _)| changed_names.contains(name))
+				.map(|(name, versions)| (name, &versions.effective.info.version)),
+				changed_names.iter().cloned().filter(|name| !remaining_mods.contains_key(name)),
+				global_options.enable_new_mods)
+			.chain_err(|| "Could not update mod-list.json")?;
+
+			println!("    Updating mod-list.json ... done");
+		}
+
+		Ok(())
+	}
+}
+
+/// Converts the given solved mods into installs into the given mods directory, sorted by name and version.
+fn installs(
+	mods_directory: &::std::path::Path,
+	to_install: ::std::collections::HashMap<::factorio_mods_common::ModName, SolvedMod>,
+) -> ::Result<Vec<Install>> {
+	::itertools::Itertools::sorted_by(to_install.into_iter(), |(name1, solved_mod1), (name2, solved_mod2)|
+		name1.cmp(name2)
+		.then_with(|| solved_mod1.version.cmp(&solved_mod2.version)))
+	.into_iter()
+	.map(|(name, solved_mod)| {
+		let target = mods_directory.join(solved_mod.source.file_name().ok_or_else(|| format!("Could not parse filename {}", solved_mod.source.display()))?);
+		let sha1 = solved_mod.sha1.ok_or_else(|| format!("{} {} is not a release from the mods portal, so it cannot be installed", name, solved_mod.version))?;
+		Ok(Install {
+			name,
+			version: solved_mod.version,
+			source: solved_mod.source,
+			target,
+			sha1,
+		})
+	})
+	.collect()
+}
+
+/// Returns whether the given path is directly inside the given mods directory, and isn't `mod-list.json`
+fn is_mod_path(path: &::std::path::Path, mods_directory: &::std::path::Path) -> bool {
+	path.parent() == Some(mods_directory) &&
+	path.file_name().map_or(false, |file_name| file_name != ::std::ffi::OsStr::new("mod-list.json"))
+}
+
+/// Lists the contents of the given mods directory.
+///
+/// `mod-list.json` is excluded since enabling and disabling mods doesn't invalidate a plan.
+fn snapshot(mods_directory: &::std::path::Path) -> ::Result<Vec<DirectoryEntry>> {
+	let mut result = vec![];
+
+	for directory_entry in ::std::fs::read_dir(mods_directory).chain_err(|| format!("Could not read directory {}", mods_directory.display()))? {
+		let directory_entry = directory_entry.chain_err(|| format!("Could not read directory {}", mods_directory.display()))?;
+
+		let file_name = directory_entry.file_name().to_string_lossy().into_owned();
+		if file_name == "mod-list.json" {
+			continue;
+		}
+
+		let metadata = directory_entry.metadata().chain_err(|| format!("Could not read metadata of {}", directory_entry.path().display()))?;
+
+		let modified =
+			metadata.modified().ok()
+			.and_then(|modified| modified.duration_since(::std::time::UNIX_EPOCH).ok())
+			.map(|modified| modified.as_secs());
+
+		result.push(DirectoryEntry { file_name, len: metadata.len(), modified });
+	}
+
+	result.sort();
+
+	Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn temp_directory(name: &str) -> ::std::path::PathBuf {
+		let directory = ::std::env::temp_dir().join(format!("fac-test-{}-{}", ::std::process::id(), name));
+		let _ = ::std::fs::remove_dir_all(&directory);
+		::std::fs::create_dir_all(&directory).unwrap();
+		directory
+	}
+
+	fn plan(removal_path: ::std::path::PathBuf, source: ::std::path::PathBuf, target: ::std::path::PathBuf, sha1: String) -> Plan {
+		Plan {
+			mods_directory: Default::default(),
+			mods_directory_snapshot: vec![],
+			reqs: Default::default(),
+			upgrades: vec![],
+			removals: vec![Removal {
+				name: ::factorio_mods_common::ModName("foo".to_string()),
+				version: ::factorio_mods_common::ReleaseVersion("1.0.0".parse().unwrap()),
+				path: removal_path,
+				mod_type: ::factorio_mods_local::InstalledModType::Zipped,
+			}],
+			installs: vec![Install {
+				name: ::factorio_mods_common::ModName("foo".to_string()),
+				version: ::factorio_mods_common::ReleaseVersion("2.0.0".parse().unwrap()),
+				source,
+				target,
+				sha1,
+			}],
+		}
+	}
+
+	#[test]
+	fn check_paths() {
+		let directory = temp_directory("check_paths");
+		let mods_directory = directory.join("mods");
+		let cache_directory = directory.join("cache");
+		::std::fs::create_dir_all(&mods_directory).unwrap();
+		::std::fs::create_dir_all(&cache_directory).unwrap();
+
+		let source = cache_directory.join("foo_2.0.0.zip");
+		::std::fs::write(&source, "foo 2.0.0").unwrap();
+		let sha1 = ::download::sha1(&source).unwrap();
+
+		let removal_path = mods_directory.join("foo_1.0.0.zip");
+		let target = mods_directory.join("foo_2.0.0.zip");
+
+		plan(removal_path.clone(), source.clone(), target.clone(), sha1.clone()).check_paths(&mods_directory, &cache_directory).unwrap();
+
+		for removal_path in &[
+			directory.join("foo_1.0.0.zip"),
+			mods_directory.join("..").join("foo_1.0.0.zip"),
+			mods_directory.join(".."),
+			mods_directory.join("subdirectory").join("foo_1.0.0.zip"),
+			mods_directory.join("mod-list.json"),
+			"foo_1.0.0.zip".into(),
+		] {
+			assert!(plan(removal_path.clone(), source.clone(), target.clone(), sha1.clone()).check_paths(&mods_directory, &cache_directory).is_err(), "{}", removal_path.display());
+		}
+
+		for target in &[
+			directory.join("foo_2.0.0.zip"),
+			mods_directory.join("..").join("foo_2.0.0.zip"),
+			mods_directory.join("mod-list.json"),
+		] {
+			assert!(plan(removal_path.clone(), source.clone(), target.clone(), sha1.clone()).check_paths(&mods_directory, &cache_directory).is_err(), "{}", target.display());
+		}
+
+		let outside_cache = directory.join("foo_2.0.0.zip");
+		::std::fs::write(&outside_cache, "foo 2.0.0").unwrap();
+		assert!(plan(removal_path.clone(), outside_cache, target.clone(), sha1.clone()).check_paths(&mods_directory, &cache_directory).is_err());
+
+		::std::fs::write(&source, "something else").unwrap();
+		assert!(plan(removal_path, source, target, sha1).check_paths(&mods_directory, &cache_directory).is_err());
+
+		::std::fs::remove_dir_all(&directory).unwrap();
+	}
+}
@@ -14,7 +14,7 @@ impl ::util::SubCommand for SubCommand {
 		matches: &'a ::clap::ArgMatches<'a>,
 		local_api: ::Result<&'a ::factorio_mods_local::API>,
 		web_api: ::Result<&'a ::factorio_mods_web::API>,
-		prompt_override: Option<bool>,
+		global_options: ::util::GlobalOptions<'a>,
 	) -> Box<Future<Item = (), Error = ::Error> + 'a> {
 		Box::new(::async_block! {
 			let mods = matches.values_of("mods").unwrap();
@@ -22,14 +22,14 @@ impl ::util::SubCommand for SubCommand {
 			let local_api = local_api?;
 			let web_api = web_api?;
 
-			let mut config = ::config::Config::load(local_api)?;
+			let mut config = ::config::Config::load(local_api, global_options.wait_for_lock)?;
 
 			for mod_ in mods {
 				let name = ::factorio_mods_common::ModName(mod_.to_string());
 				config.mods.remove(&name);
 			}
 
-			::await!(::solve::compute_and_apply_diff(local_api, web_api, config, prompt_override))?;
+			::await!(::solve::compute_and_apply_diff(local_api, web_api, config, global_options))?;
 
 			Ok(())
 		})
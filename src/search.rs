@@ -1,12 +1,26 @@
 use ::futures::Future;
 
+lazy_static! {
+	static ref GAME_VERSION_REGEX: ::regex::Regex = ::regex::Regex::new(r"^\d+\.\d+$").unwrap();
+}
+
 pub struct SubCommand;
 
 impl ::util::SubCommand for SubCommand {
 	fn build_subcommand<'a>(&self, subcommand: ::clap::App<'a, 'a>) -> ::clap::App<'a, 'a> {
 		clap_app!(@app (subcommand)
-			(about: "Search the mods database.")
-			(@arg query: index(1) "search string"))
+			(about: "Search the mods database.\n\nIf a local search index exists, it is searched instead of the mods portal. \
+			         The search string can then contain typos and the prefixes name:, title:, author:, summary: and version:")
+			(@arg query: index(1) "search string")
+			(@arg sort: --sort +takes_value possible_values(&["downloads", "updated", "name"])
+				"sort results by number of downloads, last update or name. \
+				 Without a local search index, sorting by downloads fetches every matching mod from the mods portal first.")
+			(@arg author: --author +takes_value "only show mods by this author")
+			(@arg game_version: long("game-version") +takes_value "only show mods that support this game version, such as 0.16")
+			(@arg limit: --limit +takes_value "show at most this many results")
+			(@arg page: --page +takes_value requires("limit") "show this page of results, counting from 1, with --limit results per page")
+			(@arg refresh: --refresh conflicts_with("online") "rebuild the local search index from the mods portal before searching")
+			(@arg online: --online "search the mods portal even if a local search index exists"))
 	}
 
 	fn run<'a>(
@@ -14,7 +28,7 @@ impl ::util::SubCommand for SubCommand {
 		matches: &'a ::clap::ArgMatches<'a>,
 		_: ::Result<&'a ::factorio_mods_local::API>,
 		web_api: ::Result<&'a ::factorio_mods_web::API>,
-		_: Option<bool>,
+		global_options: ::util::GlobalOptions<'a>,
 	) -> Box<Future<Item = (), Error = ::Error> + 'a> {
 		use ::ResultExt;
 
@@ -22,19 +36,169 @@ impl ::util::SubCommand for SubCommand {
 		Box::new(::async_block! {
 			let query = matches.value_of("query").unwrap_or("");
 
+			let sort = matches.value_of("sort");
+
+			let author = matches.value_of("author").map(str::to_lowercase);
+
+			let game_version = matches.value_of("game_version");
+			if let Some(game_version) = game_version {
+				ensure!(GAME_VERSION_REGEX.is_match(game_version), r#"Game version "{}" must be of the form major.minor, such as 0.16"#, game_version);
+			}
+
+			let limit = match matches.value_of("limit") {
+				Some(limit) => Some(limit.parse::<usize>().chain_err(|| format!(r#"Could not parse "{}" as a number"#, limit))?),
+				None => None,
+			};
+
+			let page = match matches.value_of("page") {
+				Some(page) => {
+					let page = page.parse::<usize>().chain_err(|| format!(r#"Could not parse "{}" as a number"#, page))?;
+					ensure!(page >= 1, "Page must be at least 1");
+					page
+				},
+				None => 1,
+			};
+
+			// The results before the requested page.
+			let skip = limit.map_or(0, |limit| (page - 1) * limit);
+
 			let web_api = web_api?;
 
+			let index =
+				if matches.is_present("refresh") {
+					if global_options.output_format == ::util::OutputFormat::Text {
+						println!("Refreshing search index ...");
+					}
+
+					let mut mods = vec![];
+
+					let r: Result<_, ::factorio_mods_web::Error> = do catch {
+						#[async] for mod_ in web_api.search("") {
+							mods.push(::search_index::IndexedMod::from(&mod_));
+						}
+					};
+					r.chain_err(|| "Could not retrieve mods")?;
+
+					let index = ::search_index::SearchIndex::new(mods);
+					index.save()?;
+
+					if global_options.output_format == ::util::OutputFormat::Text {
+						println!("Refreshing search index ... done");
+						println!();
+					}
+
+					Some(index)
+				}
+				else if matches.is_present("online") {
+					None
+				}
+				else {
+					::search_index::SearchIndex::load()?
+				};
+
+			if let Some(index) = index {
+				let mut result: Vec<_> =
+					index.search(query).into_iter()
+					.filter(|mod_| author.as_ref().map_or(true, |author| mod_.owner.iter().any(|owner| &owner.to_lowercase() == author)))
+					.filter(|mod_| game_version.map_or(true, |game_version| mod_.supports_game_version(game_version)))
+					.collect();
+
+				match sort {
+					Some("downloads") => result.sort_by(|mod1, mod2| mod1.downloads_count.cmp(&mod2.downloads_count).reverse()),
+					Some("updated") => result.sort_by(|mod1, mod2| mod1.latest_release.released_at.cmp(&mod2.latest_release.released_at).reverse()),
+					Some("name") => result.sort_by(|mod1, mod2| mod1.name.cmp(&mod2.name)),
+					Some(_) => unreachable!(),
+					None => (),
+				}
+
+				let result = result.into_iter().skip(skip).take(limit.unwrap_or(usize::max_value()));
+
+				if let ::util::OutputFormat::Json = global_options.output_format {
+					let records: Vec<_> = result.map(::output::SearchResultRecord::from).collect();
+					return ::output::print_json(&records);
+				}
+
+				for mod_ in result {
+					print_mod(&mod_.title, &mod_.name, &mod_.summary);
+				}
+
+				return Ok(());
+			}
+
+			let mut search_query = ::factorio_mods_web::SearchQuery::new(query);
+
+			if let Some(game_version) = game_version {
+				search_query = search_query.game_version(game_version);
+			}
+
+			match sort {
+				Some("updated") => search_query = search_query.sort(::factorio_mods_web::SortField::UpdatedAt, ::factorio_mods_web::SortOrder::Descending),
+				Some("name") => search_query = search_query.sort(::factorio_mods_web::SortField::Name, ::factorio_mods_web::SortOrder::Ascending),
+				// The mods portal can't sort by downloads, so all results are fetched and sorted below.
+				Some("downloads") => (),
+				Some(_) => unreachable!(),
+				None => (),
+			}
+
+			// If every mod that the mods portal returns is a result, the requested page can be fetched directly.
+			// Otherwise results are filtered here, so pages of results don't line up with pages of the mods portal.
+			let server_side_paging = query.is_empty() && author.is_none() && sort != Some("downloads");
+			let mut skip = match limit {
+				Some(limit) if server_side_paging => {
+					search_query = search_query.page(page as u64).page_size(limit as u64);
+					0
+				},
+
+				_ => skip,
+			};
+
+			let mut result = vec![];
+
 			let r: Result<_, ::factorio_mods_web::Error> = do catch {
-				#[async] for mod_ in web_api.search(query) {
-					println!("{}", mod_.title);
-					println!("    Name: {}", mod_.name);
-					println!();
-					::util::wrapping_println(&mod_.summary.0, "    ");
-					println!();
+				#[async] for mod_ in web_api.search_query(search_query) {
+					if let Some(ref author) = author {
+						if !mod_.owner.iter().any(|owner| &owner.0.to_lowercase() == author) {
+							continue;
+						}
+					}
+
+					if sort != Some("downloads") && skip > 0 {
+						skip -= 1;
+						continue;
+					}
+
+					result.push(mod_);
+
+					if sort != Some("downloads") && limit.map_or(false, |limit| result.len() >= limit) {
+						break;
+					}
 				}
 			};
+			r.chain_err(|| "Could not retrieve mods")?;
 
-			r.chain_err(|| "Could not retrieve mods")
+			if sort == Some("downloads") {
+				result.sort_by(|mod1, mod2| mod1.downloads_count.cmp(&mod2.downloads_count).reverse());
+				result = result.into_iter().skip(skip).take(limit.unwrap_or(usize::max_value())).collect();
+			}
+
+			if let ::util::OutputFormat::Json = global_options.output_format {
+				let records: Vec<_> = result.iter().map(::output::SearchResultRecord::new).collect();
+				return ::output::print_json(&records);
+			}
+
+			for mod_ in result {
+				print_mod(&mod_.title.0, &mod_.name, &mod_.summary.0);
+			}
+
+			Ok(())
 		})
 	}
 }
+
+fn print_mod(title: &str, name: &::factorio_mods_common::ModName, summary: &str) {
+	println!("{}", title);
+	println!("    Name: {}", name);
+	println!();
+	::util::wrapping_println(summary, "    ");
+	println!();
+}
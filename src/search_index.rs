@@ -0,0 +1,407 @@
+//! A local snapshot of the mods portal that can be searched offline.
+
+use ::ResultExt;
+
+#[derive(Debug, ::serde_derive::Deserialize, ::serde_derive::Serialize)]
+#[serde(tag = "version")]
+enum StoredIndex<'a> {
+	V1 {
+		mods: ::std::borrow::Cow<'a, [IndexedMod]>,
+	},
+}
+
+/// A snapshot of the mods portal.
+#[derive(Debug)]
+pub struct SearchIndex {
+	mods: Vec<IndexedMod>,
+}
+
+/// A mod in the search index.
+#[derive(Clone, Debug, ::serde_derive::Deserialize, ::serde_derive::Serialize)]
+pub struct IndexedMod {
+	pub name: ::factorio_mods_common::ModName,
+	pub title: String,
+	pub owner: Vec<String>,
+	pub summary: String,
+	pub downloads_count: u64,
+	pub latest_release: IndexedRelease,
+}
+
+/// The latest release of a mod in the search index.
+#[derive(Clone, Debug, ::serde_derive::Deserialize, ::serde_derive::Serialize)]
+pub struct IndexedRelease {
+	pub version: ::factorio_mods_common::ReleaseVersion,
+	pub factorio_version: ::factorio_mods_common::ModVersionReq,
+	pub file_name: String,
+	pub released_at: String,
+	pub sha1: String,
+}
+
+impl<'a> From<&'a ::factorio_mods_web::SearchResponseMod> for IndexedMod {
+	fn from(mod_: &'a ::factorio_mods_web::SearchResponseMod) -> Self {
+		IndexedMod {
+			name: mod_.name.clone(),
+			title: mod_.title.0.clone(),
+			owner: mod_.owner.iter().map(|owner| owner.0.clone()).collect(),
+			summary: mod_.summary.0.clone(),
+			downloads_count: mod_.downloads_count.0,
+			latest_release: IndexedRelease {
+				version: mod_.latest_release.version.clone(),
+				factorio_version: mod_.latest_release.info_json.factorio_version.clone(),
+				file_name: mod_.latest_release.filename.0.clone(),
+				released_at: mod_.latest_release.released_at.0.clone(),
+				sha1: mod_.latest_release.sha1.0.clone(),
+			},
+		}
+	}
+}
+
+impl IndexedMod {
+	/// Returns whether the latest release of this mod supports the given game version of the form `0.16`
+	pub fn supports_game_version(&self, game_version: &str) -> bool {
+		let factorio_version = self.latest_release.factorio_version.to_string();
+		let factorio_version = factorio_version.trim_left_matches(|c: char| !c.is_digit(10));
+		factorio_version == game_version || factorio_version.starts_with(&format!("{}.", game_version))
+	}
+}
+
+/// A field of a mod that a search term can be restricted to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Field {
+	Name,
+	Title,
+	Author,
+	Summary,
+}
+
+/// A single term of a parsed search query.
+#[derive(Debug)]
+enum Term {
+	/// Matches the given fields, ranked by how closely they match.
+	Text(&'static [Field], String),
+
+	/// Matches mods whose latest release supports the given game version.
+	GameVersion(String),
+}
+
+const ALL_FIELDS: &[Field] = &[Field::Name, Field::Title, Field::Author, Field::Summary];
+
+impl SearchIndex {
+	/// Builds an index from the given mods.
+	pub fn new<I>(mods: I) -> Self where I: IntoIterator<Item = IndexedMod> {
+		SearchIndex { mods: mods.into_iter().collect() }
+	}
+
+	/// Loads the index from the cache directory, if it has been built.
+	pub fn load() -> ::Result<Option<Self>> {
+		let path = path()?;
+
+		match ::std::fs::File::open(&path) {
+			Ok(file) => {
+				let index: StoredIndex =
+					::serde_json::from_reader(::std::io::BufReader::new(file))
+					.chain_err(|| format!("Could not parse search index {}. Run `fac search --refresh` to rebuild it.", path.display()))?;
+				let StoredIndex::V1 { mods } = index;
+				Ok(Some(SearchIndex { mods: mods.into_owned() }))
+			},
+
+			Err(err) => match err.kind() {
+				::std::io::ErrorKind::NotFound => Ok(None),
+				_ => Err(err).chain_err(|| format!("Could not read search index {}", path.display())),
+			},
+		}
+	}
+
+	/// Saves the index to the cache directory.
+	pub fn save(&self) -> ::Result<()> {
+		let path = path()?;
+
+		let parent = path.parent().unwrap();
+		::std::fs::create_dir_all(parent).chain_err(|| format!("Could not create cache directory {}", parent.display()))?;
+
+		::factorio_mods_local::write_json_atomic(&path, ::factorio_mods_local::Backup::No, &StoredIndex::V1 { mods: ::std::borrow::Cow::Borrowed(&self.mods) })
+		.chain_err(|| format!("Could not write to search index {}", path.display()))?;
+
+		Ok(())
+	}
+
+	/// Searches the index, and returns the matching mods in order of relevance.
+	///
+	/// Each whitespace-separated word of the query must match the mod. Words can be restricted to a single field with the
+	/// `name:`, `title:`, `author:` and `summary:` prefixes. The `version:` prefix matches mods whose latest release supports the given game version.
+	/// Words are allowed to have typos, as long as they're long enough to still be recognizable.
+	pub fn search(&self, query: &str) -> Vec<&IndexedMod> {
+		let terms: Vec<_> = query.split_whitespace().filter_map(parse_term).collect();
+
+		let mut result: Vec<_> =
+			self.mods.iter()
+			.filter_map(|mod_| {
+				let mut score = 0.0;
+
+				for term in &terms {
+					match *term {
+						Term::Text(fields, ref text) => {
+							let term_score =
+								fields.iter()
+								.map(|&field| field_weight(field) * field_values(mod_, field).map(|value| match_score(text, value)).fold(0.0, f64::max))
+								.fold(0.0, f64::max);
+
+							if term_score <= 0.0 {
+								return None;
+							}
+
+							score += term_score;
+						},
+
+						Term::GameVersion(ref game_version) => if !mod_.supports_game_version(game_version) {
+							return None;
+						},
+					}
+				}
+
+				Some((mod_, score))
+			})
+			.collect();
+
+		result.sort_by(|&(mod1, score1), &(mod2, score2)|
+			score2.partial_cmp(&score1).unwrap_or(::std::cmp::Ordering::Equal)
+			.then_with(|| mod1.downloads_count.cmp(&mod2.downloads_count).reverse()));
+
+		result.into_iter().map(|(mod_, _)| mod_).collect()
+	}
+}
+
+/// Returns the path of the search index in the cache directory.
+fn path() -> ::Result<::std::path::PathBuf> {
+	Ok(::config::cache_directory()?.join("search-index.json"))
+}
+
+fn parse_term(word: &str) -> Option<Term> {
+	let (prefix, text) = match word.find(':') {
+		Some(index) => (Some(&word[..index]), &word[index + 1..]),
+		None => (None, word),
+	};
+
+	let fields: &'static [Field] = match prefix.map(str::to_lowercase).as_ref().map(String::as_str) {
+		None => ALL_FIELDS,
+		Some("name") => &[Field::Name],
+		Some("title") => &[Field::Title],
+		Some("author") | Some("owner") => &[Field::Author],
+		Some("summary") => &[Field::Summary],
+		Some("version") => return if text.is_empty() { None } else { Some(Term::GameVersion(text.to_string())) },
+
+		// Not a known prefix, so treat the whole word as text.
+		Some(_) => return Some(Term::Text(ALL_FIELDS, word.to_lowercase())),
+	};
+
+	if text.is_empty() {
+		None
+	}
+	else {
+		Some(Term::Text(fields, text.to_lowercase()))
+	}
+}
+
+fn field_weight(field: Field) -> f64 {
+	match field {
+		Field::Name => 3.0,
+		Field::Title => 2.5,
+		Field::Author => 2.0,
+		Field::Summary => 1.0,
+	}
+}
+
+fn field_values<'a>(mod_: &'a IndexedMod, field: Field) -> Box<Iterator<Item = &'a str> + 'a> {
+	match field {
+		Field::Name => Box::new(::std::iter::once(&*mod_.name.0)),
+		Field::Title => Box::new(::std::iter::once(&*mod_.title)),
+		Field::Author => Box::new(mod_.owner.iter().map(String::as_str)),
+		Field::Summary => Box::new(::std::iter::once(&*mod_.summary)),
+	}
+}
+
+/// Returns how well the given lowercase search term matches the given field value, between 0 (no match) and 1 (exact match).
+fn match_score(term: &str, value: &str) -> f64 {
+	let value = value.to_lowercase();
+
+	if value == term {
+		return 1.0;
+	}
+
+	let mut best: f64 = if value.contains(term) { 0.7 } else { 0.0 };
+
+	let max_typos = match term.chars().count() {
+		0 ... 3 => 0,
+		4 ... 6 => 1,
+		_ => 2,
+	};
+
+	for word in value.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()) {
+		let score =
+			if word == term {
+				0.9
+			}
+			else if word.starts_with(term) {
+				0.8
+			}
+			else {
+				match edit_distance(term, word, max_typos) {
+					Some(1) => 0.5,
+					Some(2) => 0.4,
+					_ => 0.0,
+				}
+			};
+
+		best = best.max(score);
+	}
+
+	best
+}
+
+/// Computes the optimal string alignment distance between the two strings, or `None` if it is greater than `max`
+fn edit_distance(s1: &str, s2: &str, max: usize) -> Option<usize> {
+	let s1: Vec<_> = s1.chars().collect();
+	let s2: Vec<_> = s2.chars().collect();
+
+	if s1.len().max(s2.len()) - s1.len().min(s2.len()) > max {
+		return None;
+	}
+
+	let mut previous_previous: Vec<usize> = vec![0; s2.len() + 1];
+	let mut previous: Vec<usize> = (0..=s2.len()).collect();
+	let mut current = vec![0; s2.len() + 1];
+
+	for i in 1..=s1.len() {
+		current[0] = i;
+
+		for j in 1..=s2.len() {
+			let cost = if s1[i - 1] == s2[j - 1] { 0 } else { 1 };
+
+			current[j] = (previous[j] + 1).min(current[j - 1] + 1).min(previous[j - 1] + cost);
+
+			if i > 1 && j > 1 && s1[i - 1] == s2[j - 2] && s1[i - 2] == s2[j - 1] {
+				current[j] = current[j].min(previous_previous[j - 2] + 1);
+			}
+		}
+
+		if current.iter().all(|&distance| distance > max) {
+			return None;
+		}
+
+		::std::mem::swap(&mut previous_previous, &mut previous);
+		::std::mem::swap(&mut previous, &mut current);
+	}
+
+	let distance = previous[s2.len()];
+	if distance <= max { Some(distance) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn indexed_mod(name: &str, title: &str, owner: &str, downloads_count: u64, factorio_version: &str) -> IndexedMod {
+		IndexedMod {
+			name: ::factorio_mods_common::ModName(name.to_string()),
+			title: title.to_string(),
+			owner: vec![owner.to_string()],
+			summary: String::new(),
+			downloads_count,
+			latest_release: IndexedRelease {
+				version: ::factorio_mods_common::ReleaseVersion(::semver::Version::parse("1.0.0").unwrap()),
+				factorio_version: ::factorio_mods_common::ModVersionReq(::semver::VersionReq::parse(factorio_version).unwrap()),
+				file_name: format!("{}_1.0.0.zip", name),
+				released_at: String::new(),
+				sha1: String::new(),
+			},
+		}
+	}
+
+	#[test]
+	fn test_edit_distance() {
+		for &(s1, s2, max, expected) in &[
+			("abc", "abc", 0, Some(0)),
+			("abc", "abd", 0, None),
+			("abc", "abd", 1, Some(1)),
+			("abc", "abcd", 1, Some(1)),
+			("abc", "abcde", 1, None),
+			("abc", "acb", 1, Some(1)),
+			("kitten", "sitting", 2, None),
+			("kitten", "sitting", 3, Some(3)),
+
+			// Optimal string alignment doesn't edit a substring more than once, so this is 3 rather than the Damerau-Levenshtein distance of 2.
+			("ca", "abc", 2, None),
+			("ca", "abc", 3, Some(3)),
+		] {
+			assert_eq!(edit_distance(s1, s2, max), expected, "{} {} {}", s1, s2, max);
+		}
+	}
+
+	#[test]
+	fn test_parse_term() {
+		fn text(word: &str) -> Option<(&'static [Field], String)> {
+			match parse_term(word) {
+				Some(Term::Text(fields, text)) => Some((fields, text)),
+				Some(term) => panic!("{} was parsed as {:?}", word, term),
+				None => None,
+			}
+		}
+
+		assert_eq!(text("Bob"), Some((ALL_FIELDS, "bob".to_string())));
+		assert_eq!(text("author:Bobingabout"), Some((&[Field::Author][..], "bobingabout".to_string())));
+		assert_eq!(text("owner:bobingabout"), Some((&[Field::Author][..], "bobingabout".to_string())));
+		assert_eq!(text("NAME:bob"), Some((&[Field::Name][..], "bob".to_string())));
+		assert_eq!(text("foo:bar"), Some((ALL_FIELDS, "foo:bar".to_string())));
+		assert_eq!(text("author:"), None);
+
+		match parse_term("version:0.16") {
+			Some(Term::GameVersion(ref game_version)) => assert_eq!(game_version, "0.16"),
+			term => panic!("version:0.16 was parsed as {:?}", term),
+		}
+
+		assert!(parse_term("version:").is_none());
+	}
+
+	#[test]
+	#[cfg_attr(feature = "cargo-clippy", allow(float_cmp))]
+	fn test_match_score() {
+		for &(term, value, expected) in &[
+			("bob", "Bob", 1.0),
+			("bob", "Bob's Mods", 0.9),
+			("bob", "bobinserters", 0.8),
+			("inserter", "bobinserters", 0.7),
+			("insertre", "Bob's Inserter", 0.5),
+			("insetrre", "Bob's Inserter", 0.4),
+
+			// Words of three characters or less must match exactly.
+			("bop", "bob", 0.0),
+			("bob", "angel", 0.0),
+		] {
+			assert_eq!(match_score(term, value), expected, "{} {}", term, value);
+		}
+	}
+
+	#[test]
+	fn test_search() {
+		let index = SearchIndex::new(vec![
+			indexed_mod("bobinserters", "Bob's Adjustable Inserters", "Bobingabout", 100, "0.16"),
+			indexed_mod("inserter-throughput", "Inserter Throughput", "someone", 1000, "0.17"),
+			indexed_mod("angelsrefining", "Angel's Refining", "Arch666Angel", 500, "0.16"),
+			indexed_mod("boblibrary", "Bob's Functions Library", "Bobingabout", 5000, "0.16"),
+		]);
+
+		let search = |query| index.search(query).into_iter().map(|mod_| &*mod_.name.0).collect::<Vec<_>>();
+
+		// An exact word of the name ranks above a substring of the name.
+		assert_eq!(search("inserter"), ["inserter-throughput", "bobinserters"]);
+
+		assert_eq!(search("inserter version:0.16"), ["bobinserters"]);
+
+		// Mods that match equally well are ordered by their number of downloads.
+		assert_eq!(search("author:bobingabout"), ["boblibrary", "bobinserters"]);
+
+		assert_eq!(search("refinnig"), ["angelsrefining"]);
+
+		assert!(search("bob refining").is_empty());
+	}
+}
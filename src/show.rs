@@ -6,6 +6,7 @@ impl ::util::SubCommand for SubCommand {
 	fn build_subcommand<'a>(&self, subcommand: ::clap::App<'a, 'a>) -> ::clap::App<'a, 'a> {
 		clap_app!(@app (subcommand)
 			(about: "Show details about specific mods.")
+			(@arg full: --full "also show the description, changelog, tags, license and other details, and the dependencies of each release")
 			(@arg mods: ... +required index(1) "mods to show"))
 	}
 
@@ -14,7 +15,7 @@ impl ::util::SubCommand for SubCommand {
 		matches: &'a ::clap::ArgMatches<'a>,
 		_: ::Result<&'a ::factorio_mods_local::API>,
 		web_api: ::Result<&'a ::factorio_mods_web::API>,
-		_: Option<bool>,
+		global_options: ::util::GlobalOptions<'a>,
 	) -> Box<Future<Item = (), Error = ::Error> + 'a> {
 		use ::ResultExt;
 
@@ -24,37 +25,106 @@ impl ::util::SubCommand for SubCommand {
 			let names = matches.values_of("mods").unwrap();
 			let names = names.map(|name| ::factorio_mods_common::ModName(name.to_string()));
 
+			if matches.is_present("full") {
+				let mods =
+					stream::futures_ordered(names.map(|name| ::async_block! {
+						::await!(web_api.get_full(&name))
+						.chain_err(|| format!("Could not retrieve mod {}", name))
+					}));
+
+				if let ::util::OutputFormat::Json = global_options.output_format {
+					let mut result = vec![];
+					#[async] for mod_ in mods {
+						result.push(mod_);
+					}
+
+					let records: Vec<_> = result.iter().map(::output::ModRecord::new_full).collect();
+					::output::print_json(&records)?;
+					return Ok(());
+				}
+
+				#[async] for mod_ in mods {
+					print_summary(&mod_.name, &mod_.owner, &mod_.title, &mod_.summary);
+
+					if let Some(ref category) = mod_.category {
+						println!("Category: {}", category);
+					}
+
+					if !mod_.tags.is_empty() {
+						println!("Tags: {}", ::itertools::join(mod_.tags.iter().map(|tag| &tag.title), ", "));
+					}
+
+					if let Some(ref homepage) = mod_.homepage {
+						if !homepage.0.is_empty() {
+							println!("Homepage: {}", homepage);
+						}
+					}
+
+					if let Some(ref github_path) = mod_.github_path {
+						if !github_path.0.is_empty() {
+							println!("GitHub: https://github.com/{}", github_path);
+						}
+					}
+
+					if let Some(ref license) = mod_.license {
+						match license.url {
+							Some(ref url) if !url.0.is_empty() => println!("License: {} ({})", license.title, url),
+							_ => println!("License: {}", license.title),
+						}
+					}
+
+					if let Some(ref thumbnail) = mod_.thumbnail {
+						println!("Thumbnail: {}", thumbnail);
+					}
+
+					println!("Created: {}", mod_.created_at);
+
+					if let Some(ref updated_at) = mod_.updated_at {
+						println!("Updated: {}", updated_at);
+					}
+
+					print_releases(&mod_.releases);
+
+					if let Some(ref description) = mod_.description {
+						if !description.0.is_empty() {
+							println!("Description:");
+							::util::wrapping_println(&description.0, "    ");
+						}
+					}
+
+					if let Some(ref changelog) = mod_.changelog {
+						if !changelog.0.is_empty() {
+							println!("Changelog:");
+							::util::wrapping_println(&changelog.0, "    ");
+						}
+					}
+
+					println!();
+				}
+
+				return Ok(());
+			}
+
 			let mods =
 				stream::futures_ordered(names.map(|name| ::async_block! {
 					::await!(web_api.get(&name))
 					.chain_err(|| format!("Could not retrieve mod {}", name))
 				}));
 
-			#[async] for mod_ in mods {
-				println!("Name: {}", mod_.name);
-				println!("Author: {}", ::itertools::join(mod_.owner, ", "));
-				println!("Title: {}", mod_.title);
-				println!("Summary: {}", mod_.summary);
-
-				let releases = mod_.releases;
-
-				if releases.is_empty() {
-					println!("Releases:");
-					println!("    No releases");
+			if let ::util::OutputFormat::Json = global_options.output_format {
+				let mut result = vec![];
+				#[async] for mod_ in mods {
+					result.push(mod_);
 				}
-				else {
-					let mut game_versions: ::std::collections::BTreeSet<_> = Default::default();
-					for release in &releases {
-						game_versions.insert(format!("{}", release.info_json.factorio_version));
-					}
-					println!("Game versions: {}", ::itertools::join(game_versions, ", "));
 
-					println!("Releases:");
-					for release in releases {
-						println!("    Version: {:-9} Game version: {:-9}", release.version, release.info_json.factorio_version);
-					}
-				}
+				let records: Vec<_> = result.iter().map(::output::ModRecord::new).collect();
+				::output::print_json(&records)?;
+				return Ok(());
+			}
 
+			#[async] for mod_ in mods {
+				print_summary(&mod_.name, &mod_.owner, &mod_.title, &mod_.summary);
+				print_releases(&mod_.releases);
 				println!();
 			}
 
@@ -62,3 +132,44 @@ impl ::util::SubCommand for SubCommand {
 		})
 	}
 }
+
+fn print_summary(
+	name: &::factorio_mods_common::ModName,
+	owner: &[::factorio_mods_common::AuthorName],
+	title: &::factorio_mods_common::ModTitle,
+	summary: &::factorio_mods_web::ModSummary,
+) {
+	println!("Name: {}", name);
+	println!("Author: {}", ::itertools::join(owner, ", "));
+	println!("Title: {}", title);
+	println!("Summary: {}", summary);
+}
+
+fn print_releases(releases: &[::factorio_mods_web::ModRelease]) {
+	if releases.is_empty() {
+		println!("Releases:");
+		println!("    No releases");
+	}
+	else {
+		let mut game_versions: ::std::collections::BTreeSet<_> = Default::default();
+		for release in releases {
+			game_versions.insert(format!("{}", release.info_json.factorio_version));
+		}
+		println!("Game versions: {}", ::itertools::join(game_versions, ", "));
+
+		println!("Releases:");
+		for release in releases {
+			println!("    Version: {:-9} Game version: {:-9}", release.version, release.info_json.factorio_version);
+
+			if let Some(ref dependencies) = release.info_json.dependencies {
+				for dependency in dependencies {
+					println!(
+						"        {} {} {}",
+						if dependency.required { "Requires" } else { "Optional" },
+						dependency.name,
+						dependency.version);
+				}
+			}
+		}
+	}
+}
@@ -1,390 +1,465 @@
-use ::futures::{ Async, Future, Poll, Stream };
+use ::futures::{ Async, Future, Poll };
 use ::ResultExt;
 
 /// Computes which old mods to uninstall and which new mods to install based on the given reqs.
-/// Asks the user for confirmation, then applies the diff.
+/// Asks the user for confirmation, then downloads the new mods and applies the diff.
 ///
 /// Returns true if the diff was successfully applied or empty.
 pub fn compute_and_apply_diff<'a>(
 	local_api: &'a ::factorio_mods_local::API,
 	web_api: &'a ::factorio_mods_web::API,
-	mut config: ::config::Config,
-	prompt_override: Option<bool>,
-) -> impl Future<Item = (), Error = ::Error> + 'a {
+	config: ::config::Config,
+	global_options: ::util::GlobalOptions<'a>,
+) -> impl Future<Item = bool, Error = ::Error> + 'a {
 	::async_block! {
-		let user_credentials = ::await!(::util::ensure_user_credentials(local_api, web_api, prompt_override))?;
+		let linked_mods = linked_mods(local_api, &config)?;
+
+		let problem = Problem::new(local_api.game_version(), config.mods.clone(), linked_mods, vec![]);
+
+		if !::await!(solve_and_apply(local_api, web_api, problem, config.mods.clone(), false, global_options))? {
+			return Ok(false);
+		}
+
+		config.save()?;
+
+		Ok(true)
+	}
+}
+
+/// Installs the missing required dependencies of the given installed mods. Asks the user for confirmation, then downloads the new mods
+/// and installs them.
+///
+/// The given mods and all other installed mods are kept at the versions that are installed, so nothing is upgraded or removed.
+/// Only the missing dependencies, and any dependencies of those that aren't installed either, are fetched from the mods portal.
+/// `reqs` are the reqs of the config, which are saved with the plan unchanged.
+///
+/// Returns true if the new mods were successfully installed, or if there was nothing to install.
+pub fn install_missing_dependencies<'a>(
+	local_api: &'a ::factorio_mods_local::API,
+	web_api: &'a ::factorio_mods_web::API,
+	dependents: Vec<::factorio_mods_local::InstalledMod>,
+	reqs: ::std::collections::HashMap<::factorio_mods_common::ModName, ::factorio_mods_common::ModVersionReq>,
+	global_options: ::util::GlobalOptions<'a>,
+) -> impl Future<Item = bool, Error = ::Error> + 'a {
+	::async_block! {
+		let dependent_names: ::std::collections::HashSet<_> = dependents.iter().map(|installed_mod| installed_mod.info.name.clone()).collect();
+
+		let installed_mods =
+			local_api.effective_mods().chain_err(|| "Could not enumerate installed mods")?
+			.into_iter()
+			.filter(|(name, _)| !dependent_names.contains(name))
+			.map(|(_, versions)| versions.effective)
+			.collect();
+
+		let problem = Problem::new(local_api.game_version(), Default::default(), dependents, installed_mods);
+
+		::await!(solve_and_apply(local_api, web_api, problem, reqs, true, global_options))
+	}
+}
+
+/// Solves the given problem, asks the user for confirmation, then downloads the new mods and applies the resulting plan.
+///
+/// If `installs_only` is true, the plan only installs the solved mods that aren't installed yet and doesn't remove anything.
+/// Otherwise it also removes every installed mod that isn't part of the solution. `reqs` are saved with the plan.
+///
+/// Returns true if the plan was successfully applied or empty.
+fn solve_and_apply<'a>(
+	local_api: &'a ::factorio_mods_local::API,
+	web_api: &'a ::factorio_mods_web::API,
+	problem: Problem,
+	reqs: ::std::collections::HashMap<::factorio_mods_common::ModName, ::factorio_mods_common::ModVersionReq>,
+	installs_only: bool,
+	global_options: ::util::GlobalOptions<'a>,
+) -> impl Future<Item = bool, Error = ::Error> + 'a {
+	::async_block! {
+		let user_credentials = ::await!(::util::ensure_user_credentials(local_api, web_api, global_options.prompt_override))?;
 
 		let game_version = local_api.game_version();
 
-		let cache_directory = config.cache_directory()?;
+		let cache_directory = ::config::cache_directory()?;
 		::std::fs::create_dir_all(&cache_directory)
 		.chain_err(|| format!("Could not create cache directory {}", cache_directory.display()))?;
 
 		let cache_directory_canonicalized = cache_directory.canonicalize().chain_err(|| format!("Could not canonicalize {}", cache_directory.display()))?;
 
+		let download_manager = ::std::rc::Rc::new(::download::DownloadManager::new(
+			web_api, user_credentials, cache_directory.clone(), cache_directory_canonicalized, global_options.max_concurrent_downloads));
+
 		println!("Updating cache ...");
 
-		let solution_future = SolutionFuture::new(web_api, user_credentials, game_version, config.mods, cache_directory, cache_directory_canonicalized);
-		let (solution, mut reqs) = ::await!(solution_future)?;
+		let solution_future = SolutionFuture::new(web_api, download_manager.clone(), game_version, problem);
+		let solution = ::await!(solution_future)?;
 
-		reqs.remove(&::factorio_mods_common::ModName("base".to_string()));
-		config.mods = reqs;
+		let mut releases = ::std::collections::HashMap::new();
+		let mut solved_mods = ::std::collections::HashMap::new();
 
-		let solution =
-			solution
-			.ok_or("No solution found.")?
-			.into_iter()
-			.filter_map(|(name, installable)|
-				if let Installable::Mod(cached_mod) = installable {
-					Some((name, cached_mod))
-				}
-				else {
-					None
-				})
-			.collect();
+		for (name, installable) in solution.ok_or("No solution found.")? {
+			match installable {
+				Installable::Base(..) => (),
 
-		let (to_uninstall, to_install) = match compute_diff(solution, local_api, prompt_override)? {
-			Some(diff) => diff,
-			None => return Ok(()),
-		};
-
-		for installed_mod in to_uninstall {
-			let path = installed_mod.path;
-
-			match installed_mod.mod_type {
-				::factorio_mods_local::InstalledModType::Zipped => {
-					println!(
-						"    Removing {} {} ... removing file {} ...",
-						installed_mod.info.name, installed_mod.info.version,
-						path.display());
-					::std::fs::remove_file(&path)
-					.chain_err(|| format!("Could not remove file {}", path.display()))?;
+				Installable::Mod { release, .. } => {
+					solved_mods.insert(name.clone(), ::plan::SolvedMod {
+						version: release.version.clone(),
+						source: cache_directory.join(&release.filename.0),
+						sha1: Some(release.sha1.0.to_lowercase()),
+					});
+					releases.insert(name, release);
 				},
 
-				::factorio_mods_local::InstalledModType::Unpacked => {
-					println!(
-						"    Removing {} {} ... removing directory {} ...",
-						installed_mod.info.name, installed_mod.info.version,
-						path.display());
-					::std::fs::remove_dir_all(&path)
-					.chain_err(|| format!("Could not remove directory {}", path.display()))?;
+				// Local mods are already installed, so the plan keeps them and only removes other installed versions of them.
+				Installable::Local(installed_mod) => {
+					solved_mods.insert(name, ::plan::SolvedMod {
+						version: installed_mod.info.version,
+						source: installed_mod.path,
+						sha1: None,
+					});
 				},
 			}
-
-			println!(
-				"    Removing {} {} ... done",
-				installed_mod.info.name, installed_mod.info.version);
 		}
 
-		let mods_directory = local_api.mods_directory();
+		let plan =
+			if installs_only {
+				::plan::Plan::new_installs_only(solved_mods, local_api, &reqs)?
+			}
+			else {
+				::plan::Plan::new(solved_mods, local_api, &reqs)?
+			};
 
-		for cached_mod in to_install {
-			let target = mods_directory.join(cached_mod.path.file_name().unwrap());
+		plan.print();
 
-			println!("    Installing {} {} ... copying to {}", cached_mod.info.name, cached_mod.info.version, target.display());
+		// Only the releases that are going to be installed need to be downloaded.
+		let to_download: Vec<_> =
+			plan.installs.iter()
+			.filter(|install| !install.source.exists())
+			.map(|install| (install.name.clone(), releases.remove(&install.name).unwrap()))
+			.collect();
 
-			let _ =
-				::std::fs::copy(&cached_mod.path, &target)
-				.chain_err(|| format!("Could not copy file {} to {}", cached_mod.path.display(), target.display()))?;
+		if global_options.dry_run {
+			if let Some(path) = global_options.save_plan {
+				// A saved plan must be applicable later without network access, so download everything it needs now.
+				::await!(download_manager.download_all(&to_download))?;
 
-			println!("    Installing {} {} ... done", cached_mod.info.name, cached_mod.info.version);
+				let path = ::std::path::Path::new(path);
+				plan.save(path)?;
+				println!("Saved plan to {}", path.display());
+			}
+
+			return Ok(false);
 		}
 
-		config.save()?;
+		if !plan.is_empty() && !::util::prompt_continue(global_options.prompt_override)? {
+			return Ok(false);
+		}
+
+		::await!(download_manager.download_all(&to_download))?;
+
+		plan.apply(local_api, global_options)?;
 
-		Ok(())
+		Ok(true)
 	}
 }
 
-fn compute_diff(
-	mut solution: ::std::collections::HashMap<::factorio_mods_common::ModName, ::factorio_mods_local::InstalledMod>,
-	local_api: &::factorio_mods_local::API,
-	prompt_override: Option<bool>,
-) -> ::Result<Option<(Vec<::factorio_mods_local::InstalledMod>, Vec<::factorio_mods_local::InstalledMod>)>> {
-	let all_installed_mods: ::Result<::multimap::MultiMap<_, _>> =
-		local_api.installed_mods().chain_err(|| "Could not enumerate installed mods")?
-		.map(|mod_|
-			mod_
-			.map(|mod_| (mod_.info.name.clone(), mod_))
-			.chain_err(|| "Could not process an installed mod"))
-		.collect();
-
-	let all_installed_mods = all_installed_mods.chain_err(|| "Could not enumerate installed mods")?;
-
-	let mut to_uninstall = vec![];
-	let mut to_install = ::std::collections::HashMap::new();
-
-	for (name, installed_mods) in all_installed_mods {
-		match solution.remove(&name) {
-			Some(cached_mod) => {
-				let mut already_installed = false;
-
-				for installed_mod in installed_mods {
-					if cached_mod.info.version == installed_mod.info.version {
-						already_installed = true;
-					}
-					else {
-						to_uninstall.push(installed_mod);
-					}
-				}
+/// Parses the working copies of the mods that are linked into the mods directory with `fac dev link`
+///
+/// The returned mods have the paths of their links in the mods directory. Fails if a link is missing, such as when the version
+/// in the working copy's `info.json` has changed since it was linked.
+fn linked_mods(local_api: &::factorio_mods_local::API, config: &::config::Config) -> ::Result<Vec<::factorio_mods_local::InstalledMod>> {
+	let mut result = vec![];
 
-				if !already_installed {
-					to_install.insert(name, cached_mod);
-				}
-			},
+	for (name, path) in &config.dev_links {
+		let linked_mod =
+			::factorio_mods_local::InstalledMod::parse(path.clone())
+			.chain_err(|| format!("Could not parse the working copy of {} at {}", name, path.display()))?;
 
-			None =>
-				to_uninstall.extend(installed_mods),
-		}
-	}
+		let link_path = local_api.mods_directory().join(format!("{}_{}", linked_mod.info.name, linked_mod.info.version));
 
-	to_install.extend(solution);
-
-	{
-		let to_upgrade =
-			::itertools::Itertools::sorted_by(
-				to_uninstall.iter().filter_map(|installed_mod|
-					to_install.get(&installed_mod.info.name)
-					.map(|cached_mod| (installed_mod, cached_mod))),
-				|&(installed_mod1, cached_mod1), &(installed_mod2, cached_mod2)|
-					installed_mod1.info.name.cmp(&installed_mod2.info.name)
-					.then_with(|| installed_mod1.info.version.cmp(&installed_mod2.info.version))
-					.then_with(|| cached_mod1.info.name.cmp(&cached_mod2.info.name))
-					.then_with(|| cached_mod1.info.version.cmp(&cached_mod2.info.version)));
-
-		if !to_upgrade.is_empty() {
-			println!();
-			println!("The following mods will be upgraded:");
-			for (installed_mod, cached_mod) in to_upgrade {
-				println!("    {} {} -> {}", installed_mod.info.name, installed_mod.info.version, cached_mod.info.version);
-			}
-		}
+		ensure!(
+			::std::fs::symlink_metadata(&link_path).is_ok(),
+			"The working copy of {} at {} is not linked as {}. Run `fac dev link {}` again.",
+			name, path.display(), link_path.display(), path.display());
+
+		result.push(::factorio_mods_local::InstalledMod { path: link_path, ..linked_mod });
 	}
 
-	to_uninstall.sort_by(|installed_mod1, installed_mod2|
-		installed_mod1.info.name.cmp(&installed_mod2.info.name)
-		.then_with(|| installed_mod1.info.version.cmp(&installed_mod2.info.version)));
+	Ok(result)
+}
 
-	let to_install =
-		::itertools::Itertools::sorted_by(to_install.into_iter().map(|(_, cached_mod)| cached_mod), |cached_mod1, cached_mod2|
-			cached_mod1.info.name.cmp(&cached_mod2.info.name)
-			.then_with(|| cached_mod1.info.version.cmp(&cached_mod2.info.version)));
+/// The packages and reqs that the solver starts with, before anything has been fetched from the mods portal.
+struct Problem {
+	packages: Vec<Installable>,
+	already_fetching: ::std::collections::HashSet<::std::rc::Rc<::factorio_mods_common::ModName>>,
+	queued: Vec<::std::rc::Rc<::factorio_mods_common::ModName>>,
+	reqs: ::std::collections::HashMap<::factorio_mods_common::ModName, ::factorio_mods_common::ModVersionReq>,
+}
 
-	if !to_uninstall.is_empty() {
-		println!();
-		println!("The following mods will be removed:");
-		for installed_mod in &to_uninstall {
-			println!("    {} {}", installed_mod.info.name, installed_mod.info.version);
+impl Problem {
+	/// Sets up a problem for the given reqs.
+	///
+	/// `pinned_mods` are installed mods that are fixed to their installed version. They are not fetched from the mods portal, but their
+	/// required dependencies are.
+	///
+	/// `installed_mods` are installed mods that can satisfy dependencies at their installed version. They are not fetched from
+	/// the mods portal and neither are their dependencies, and they are only part of the solution if something depends on them.
+	fn new(
+		game_version: &::factorio_mods_common::ReleaseVersion,
+		mut reqs: ::std::collections::HashMap<::factorio_mods_common::ModName, ::factorio_mods_common::ModVersionReq>,
+		pinned_mods: Vec<::factorio_mods_local::InstalledMod>,
+		installed_mods: Vec<::factorio_mods_local::InstalledMod>,
+	) -> Self {
+		let mut result = Problem {
+			packages: vec![Installable::Base(::factorio_mods_common::ModName("base".to_string()), game_version.clone())],
+			already_fetching: Default::default(),
+			queued: Default::default(),
+			reqs: Default::default(),
+		};
+
+		for installed_mod in pinned_mods.iter().chain(&installed_mods) {
+			result.already_fetching.insert(installed_mod.info.name.clone().into());
 		}
-	}
 
-	if !to_install.is_empty() {
-		println!();
-		println!("The following new mods will be installed:");
-		for cached_mod in &to_install {
-			println!("    {} {}", cached_mod.info.name, cached_mod.info.version);
+		for pinned_mod in pinned_mods {
+			for dep in pinned_mod.info.dependencies.iter().filter(|dep| dep.required && dep.name.0 != "base") {
+				get(dep.name.clone().into(), &mut result.already_fetching, &mut result.queued);
+			}
+
+			reqs.insert(pinned_mod.info.name.clone(), ::factorio_mods_common::ModVersionReq(::semver::VersionReq::exact(&pinned_mod.info.version.0)));
+			result.packages.push(Installable::Local(pinned_mod));
 		}
-	}
 
-	println!();
+		result.packages.extend(installed_mods.into_iter().map(Installable::Local));
 
-	if to_uninstall.is_empty() && to_install.is_empty() {
-		println!("Nothing to do.");
-	}
-	else if !::util::prompt_continue(prompt_override)? {
-		return Ok(None);
-	}
+		for mod_name in reqs.keys() {
+			get(mod_name.clone().into(), &mut result.already_fetching, &mut result.queued);
+		}
+
+		reqs.insert(::factorio_mods_common::ModName("base".to_string()), ::factorio_mods_common::ModVersionReq(::semver::VersionReq::exact(&game_version.0)));
 
-	Ok(Some((to_uninstall, to_install)))
+		result.reqs = reqs;
+
+		result
+	}
 }
 
 struct SolutionFuture<'a> {
 	packages: Vec<Installable>,
 	already_fetching: ::std::collections::HashSet<::std::rc::Rc<::factorio_mods_common::ModName>>,
+	queued: Vec<::std::rc::Rc<::factorio_mods_common::ModName>>,
+
+	/// Mods whose releases need to be fetched again with `API::get_full` because `API::get_many` didn't return their dependencies.
+	queued_full: ::std::collections::VecDeque<::std::rc::Rc<::factorio_mods_common::ModName>>,
+
 	pending: Vec<CacheFuture>,
 	web_api: &'a ::factorio_mods_web::API,
-	user_credentials: ::factorio_mods_common::UserCredentials,
+	download_manager: ::std::rc::Rc<::download::DownloadManager<'a>>,
 	game_version: &'a ::factorio_mods_common::ReleaseVersion,
 	reqs: ::std::collections::HashMap<::factorio_mods_common::ModName, ::factorio_mods_common::ModVersionReq>,
-	cache_directory: ::std::path::PathBuf,
-	cache_directory_canonicalized: ::std::path::PathBuf,
 }
 
 impl<'a> SolutionFuture<'a> {
 	fn new(
 		web_api: &'a ::factorio_mods_web::API,
-		user_credentials: ::factorio_mods_common::UserCredentials,
+		download_manager: ::std::rc::Rc<::download::DownloadManager<'a>>,
 		game_version: &'a ::factorio_mods_common::ReleaseVersion,
-		mut reqs: ::std::collections::HashMap<::factorio_mods_common::ModName, ::factorio_mods_common::ModVersionReq>,
-		cache_directory: ::std::path::PathBuf,
-		cache_directory_canonicalized: ::std::path::PathBuf,
+		problem: Problem,
 	) -> Self {
-		let packages = vec![Installable::Base(::factorio_mods_common::ModName("base".to_string()), game_version.clone())];
+		let Problem { packages, already_fetching, queued, reqs } = problem;
 
-		let mut result = SolutionFuture {
+		SolutionFuture {
 			packages,
-			already_fetching: Default::default(),
+			already_fetching,
+			queued,
+			queued_full: Default::default(),
 			pending: Default::default(),
 			web_api,
-			user_credentials,
+			download_manager,
 			game_version,
-			reqs: Default::default(),
-			cache_directory,
-			cache_directory_canonicalized,
-		};
+			reqs,
+		}
+	}
 
-		for mod_name in reqs.keys() {
-			get(mod_name.clone().into(), &mut result.already_fetching, &mut result.pending, web_api);
+	/// Adds the releases of the given mod that are compatible with the game version as packages.
+	///
+	/// `API::get_many` doesn't always return the dependencies of releases, so if `full` is false and any compatible release is missing them,
+	/// the mod is fetched again with `API::get_full` instead. If even that doesn't return the dependencies of a release, the release is
+	/// downloaded so that they can be read from its `info.json`
+	fn add_releases(&mut self, mod_name: ::std::rc::Rc<::factorio_mods_common::ModName>, releases: Vec<::factorio_mods_web::ModRelease>, full: bool) {
+		let releases: Vec<_> =
+			releases.into_iter()
+			.filter(|release| release.info_json.factorio_version.0.matches(&self.game_version.0))
+			.collect();
+
+		if !full && releases.iter().any(|release| release.info_json.dependencies.is_none()) {
+			self.queued_full.push_back(mod_name);
+			return;
 		}
 
-		reqs.insert(::factorio_mods_common::ModName("base".to_string()), ::factorio_mods_common::ModVersionReq(::semver::VersionReq::exact(&game_version.0)));
+		println!("    Getting {} ... done", mod_name);
 
-		result.reqs = reqs;
+		for mut release in releases {
+			match release.info_json.dependencies.take() {
+				Some(dependencies) => self.add_package(&mod_name, release, dependencies),
 
-		result
+				None => {
+					let f = self.download_manager.download(&mod_name, &release);
+					self.pending.push(CacheFuture::Download(Some((mod_name.clone(), release, f))));
+				},
+			}
+		}
+	}
+
+	/// Adds the given release as a package, and queues its required dependencies to be fetched.
+	fn add_package(
+		&mut self,
+		mod_name: &::factorio_mods_common::ModName,
+		release: ::factorio_mods_web::ModRelease,
+		dependencies: Vec<::factorio_mods_common::Dependency>,
+	) {
+		for dep in dependencies.iter().filter(|dep| dep.required && dep.name.0 != "base") {
+			get(dep.name.clone().into(), &mut self.already_fetching, &mut self.queued);
+		}
+
+		self.packages.push(Installable::Mod { name: mod_name.clone(), release, dependencies });
 	}
 }
 
 impl<'a> Future for SolutionFuture<'a> {
-	type Item = (
-		Option<::std::collections::HashMap<::factorio_mods_common::ModName, Installable>>,
-		::std::collections::HashMap<::factorio_mods_common::ModName, ::factorio_mods_common::ModVersionReq>,
-	);
+	type Item = Option<::std::collections::HashMap<::factorio_mods_common::ModName, Installable>>;
 	type Error = ::Error;
 
 	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-		let mut i = 0;
-
-		while i < self.pending.len() {
-			let mut new = vec![];
-
-			match &mut self.pending[i] {
-				CacheFuture::Get(get) => match get {
-					Some((mod_name, f)) => match f.poll() {
-						Ok(Async::Ready(mod_)) => {
-							let (mod_name, _) = get.take().unwrap();
-
-							println!("    Getting {} ... done", mod_name);
-
-							for release in mod_.releases {
-								if !release.info_json.factorio_version.0.matches(&self.game_version.0) {
-									continue;
-								}
-
-								println!("        Downloading {} {} ... downloading to cache", mod_name, release.version);
-
-								let filename = self.cache_directory.join(&release.filename.0);
-								let displayable_filename = filename.display().to_string();
-
-								if filename.exists() {
-									println!("        Downloading {} {} ... parsing", mod_name, release.version);
-									parse_cached_mod(filename, &displayable_filename, &mut self.already_fetching, &mut new, &mut self.packages, self.web_api)?;
-									continue;
-								}
-
-								let mut download_filename: ::std::ffi::OsString =
-									filename.file_name()
-									.ok_or_else(|| format!("Could not parse filename {}", displayable_filename))?
-									.into();
-
-								download_filename.push(".new");
-								let download_filename = filename.with_file_name(download_filename);
-								let download_displayable_filename = download_filename.display().to_string();
-
-								{
-									let parent = download_filename.parent().ok_or_else(|| format!("Filename {} is malformed", download_displayable_filename))?;
-									let parent_canonicalized = parent.canonicalize().chain_err(|| format!("Filename {} is malformed", download_displayable_filename))?;
-									ensure!(parent_canonicalized == self.cache_directory_canonicalized, "Filename {} is malformed", download_displayable_filename);
-								}
-
-								let mut download_file = ::std::fs::OpenOptions::new();
-								let download_file = download_file.create(true).truncate(true).write(true);
-								let download_file = download_file.open(&download_filename).chain_err(|| format!("Could not open {} for writing", download_displayable_filename))?;
-								let download_file = ::std::io::BufWriter::new(download_file);
-
-								let chunk_stream = Box::new(self.web_api.download(&release, &self.user_credentials));
-
-								new.push(CacheFuture::Download(Some(DownloadFuture {
-									mod_name: mod_name.clone(),
-									release_version: release.version,
-									chunk_stream,
-									download_file,
-									download_filename,
-									download_displayable_filename,
-									filename,
-									displayable_filename,
-								})));
-							}
+		loop {
+			let mut i = 0;
+
+			while i < self.pending.len() {
+				let event = match &mut self.pending[i] {
+					CacheFuture::Get(get) => match get {
+						Some((_, f)) => match f.poll() {
+							Ok(Async::Ready(mods)) => {
+								let (mod_names, _) = get.take().unwrap();
+
+								// Mods that don't exist are not returned. Don't fail the whole process due to non-existent deps.
+								// Releases with unmet deps will be handled when computing the solution.
+								let mut releases: ::std::collections::HashMap<_, _> = mods.into_iter().map(|mod_| (mod_.name, mod_.releases)).collect();
+								let mods =
+									mod_names.into_iter()
+									.map(|mod_name| {
+										let releases = releases.remove(&*mod_name).unwrap_or_default();
+										(mod_name, releases)
+									})
+									.collect();
+								Some(Event::Fetched(mods, false))
+							},
+
+							Ok(Async::NotReady) => None,
+
+							Err(err) => {
+								let (mod_names, _) = get.take().unwrap();
+								Err(err).chain_err(|| format!("Could not get mod info for {}", ::itertools::join(mod_names.iter(), ", ")))?
+							},
 						},
 
-						Ok(Async::NotReady) => (),
+						None => unreachable!(),
+					},
+
+					CacheFuture::GetFull(get_full) => match get_full {
+						Some((_, f)) => match f.poll() {
+							Ok(Async::Ready(mod_)) => {
+								let (mod_name, _) = get_full.take().unwrap();
+								Some(Event::Fetched(vec![(mod_name, mod_.releases)], true))
+							},
+
+							Ok(Async::NotReady) => None,
 
-						Err(err) => match *err.kind() {
 							// Don't fail the whole process due to non-existent deps. Releases with unmet deps will be handled when computing the solution.
-							::factorio_mods_web::ErrorKind::StatusCode(_, ::factorio_mods_web::reqwest::StatusCode::NotFound) => {
-								let _ = get.take();
+							Err(ref err) if is_not_found(err) => {
+								let (mod_name, _) = get_full.take().unwrap();
+								Some(Event::Fetched(vec![(mod_name, vec![])], true))
 							},
 
-							_ => Err(err).chain_err(|| format!("Could not get mod info for {}", mod_name))?,
+							Err(err) => {
+								let (mod_name, _) = get_full.take().unwrap();
+								Err(err).chain_err(|| format!("Could not get mod info for {}", mod_name))?
+							},
 						},
+
+						None => unreachable!(),
 					},
 
-					None => unreachable!(),
-				},
+					CacheFuture::Download(download) => match download {
+						Some((_, _, f)) => match f.poll()? {
+							Async::Ready(filename) => {
+								let (mod_name, release, _) = download.take().unwrap();
+								Some(Event::Downloaded(mod_name, release, filename))
+							},
 
-				CacheFuture::Download(download) => match download {
-					Some(f) => loop {
-						match f.chunk_stream.poll().chain_err(|| format!("Could not download release {} {}", f.mod_name, f.release_version))? {
-							Async::Ready(Some(chunk)) =>
-								::std::io::Write::write_all(&mut f.download_file, &chunk)
-								.chain_err(|| format!("Could not write to file {}", f.download_displayable_filename))?,
+							Async::NotReady => None,
+						},
 
-							Async::Ready(None) => {
-								let DownloadFuture {
-									mod_name,
-									release_version,
-									mut download_file,
-									download_filename,
-									download_displayable_filename,
-									filename,
-									displayable_filename,
-									..
-								} = download.take().unwrap();
+						None => unreachable!(),
+					},
+				};
 
-								println!("        Downloading {} {} ... parsing", mod_name, release_version);
+				i += 1;
 
-								::std::io::Write::flush(&mut download_file)
-								.chain_err(|| format!("Could not write to file {}", download_displayable_filename))?;
-								drop(download_file);
+				match event {
+					Some(Event::Fetched(mods, full)) =>
+						for (mod_name, releases) in mods {
+							self.add_releases(mod_name, releases, full);
+						},
 
-								::std::fs::rename(&download_filename, &filename)
-								.chain_err(|| format!("Could not rename {} to {}", download_displayable_filename, displayable_filename))?;
+					Some(Event::Downloaded(mod_name, release, filename)) => {
+						let displayable_filename = filename.display().to_string();
+						let cached_mod =
+							::factorio_mods_local::InstalledMod::parse(filename)
+							.chain_err(|| format!("Could not parse {}", displayable_filename))?;
 
-								parse_cached_mod(filename.clone(), &displayable_filename, &mut self.already_fetching, &mut new, &mut self.packages, self.web_api)?;
+						self.add_package(&mod_name, release, cached_mod.info.dependencies);
+					},
 
-								println!("        Downloading {} {} ... done", mod_name, release_version);
+					None => (),
+				}
+			}
 
-								break;
-							},
+			self.pending.retain(|f| match f {
+				CacheFuture::Get(None) | CacheFuture::GetFull(None) | CacheFuture::Download(None) => false,
+				_ => true,
+			});
+
+			// New requests are only started while fewer than `MAX_CONCURRENT_REQUESTS` are in flight. The rest stay queued until one of those completes.
+			// The new futures are polled in the next iteration so that they register interest in this task.
+			let mut in_flight = self.pending.iter().filter(|f| match f {
+				CacheFuture::Get(_) | CacheFuture::GetFull(_) => true,
+				CacheFuture::Download(_) => false,
+			}).count();
+			let mut started = false;
+
+			// All the mods queued while polling the pending futures form the next frontier of the dependency graph,
+			// so they are fetched together in a single batch.
+			if !self.queued.is_empty() && in_flight < MAX_CONCURRENT_REQUESTS {
+				let mod_names = ::std::mem::replace(&mut self.queued, vec![]);
+				let f = Box::new(self.web_api.get_many(&mod_names.iter().map(|mod_name| (**mod_name).clone()).collect::<Vec<_>>()));
+				self.pending.push(CacheFuture::Get(Some((mod_names, f))));
+				in_flight += 1;
+				started = true;
+			}
 
-							Async::NotReady => break,
-						}
-					},
+			while in_flight < MAX_CONCURRENT_REQUESTS {
+				let mod_name = match self.queued_full.pop_front() {
+					Some(mod_name) => mod_name,
+					None => break,
+				};
 
-					None => unreachable!(),
-				},
+				let f = Box::new(self.web_api.get_full(&mod_name));
+				self.pending.push(CacheFuture::GetFull(Some((mod_name, f))));
+				in_flight += 1;
+				started = true;
 			}
 
-			i += 1;
-
-			self.pending.extend(new);
+			if !started {
+				break;
+			}
 		}
 
-		self.pending.retain(|f| match f {
-			CacheFuture::Get(None) | CacheFuture::Download(None) => false,
-			_ => true,
-		});
-
 		if !self.pending.is_empty() {
 			return Ok(Async::NotReady);
 		}
@@ -401,65 +476,61 @@ impl<'a> Future for SolutionFuture<'a> {
 			::package::compute_solution(packages, &reqs)
 			.chain_err(|| "Could not compute solution.")?;
 
-		Ok(Async::Ready((solution, reqs)))
+		Ok(Async::Ready(solution))
 	}
 }
 
+/// The maximum number of requests for mod information that the solver sends to the mods portal at the same time.
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+
+/// Returns whether the given error is because the mods portal doesn't have the requested mod.
+fn is_not_found(err: &::factorio_mods_web::Error) -> bool {
+	match *err.kind() {
+		::factorio_mods_web::ErrorKind::StatusCode(_, ::reqwest::StatusCode::NotFound) => true,
+		_ => false,
+	}
+}
+
+/// Queues the given mod to be fetched in the next batch, unless it has already been queued.
 fn get(
 	mod_name: ::std::rc::Rc<::factorio_mods_common::ModName>,
 	already_fetching: &mut ::std::collections::HashSet<::std::rc::Rc<::factorio_mods_common::ModName>>,
-	new: &mut Vec<CacheFuture>,
-	web_api: &::factorio_mods_web::API,
+	queued: &mut Vec<::std::rc::Rc<::factorio_mods_common::ModName>>,
 ) {
 	if already_fetching.insert(mod_name.clone()) {
 		println!("    Getting {} ...", mod_name);
 
-		let f = Box::new(web_api.get(&mod_name));
-		new.push(CacheFuture::Get(Some((mod_name, f))));
+		queued.push(mod_name);
 	}
 }
 
-fn parse_cached_mod(
-	filename: ::std::path::PathBuf,
-	displayable_filename: &str,
-	already_fetching: &mut ::std::collections::HashSet<::std::rc::Rc<::factorio_mods_common::ModName>>,
-	new: &mut Vec<CacheFuture>,
-	packages: &mut Vec<Installable>,
-	web_api: &::factorio_mods_web::API,
-) -> ::Result<()> {
-	let cached_mod =
-		::factorio_mods_local::InstalledMod::parse(filename)
-		.chain_err(|| format!("Could not parse {}", displayable_filename))?;
-
-	for dep in cached_mod.info.dependencies.iter().filter(|dep| dep.required && dep.name.0 != "base") {
-		get(dep.name.clone().into(), already_fetching, new, web_api);
-	}
-
-	packages.push(Installable::Mod(cached_mod));
-
-	Ok(())
-}
-
 enum CacheFuture {
-	Get(Option<(::std::rc::Rc<::factorio_mods_common::ModName>, Box<Future<Item = ::factorio_mods_web::Mod, Error = ::factorio_mods_web::Error>>)>),
-	Download(Option<DownloadFuture>),
+	Get(Option<(Vec<::std::rc::Rc<::factorio_mods_common::ModName>>, Box<Future<Item = Vec<::factorio_mods_web::Mod>, Error = ::factorio_mods_web::Error>>)>),
+	GetFull(Option<(::std::rc::Rc<::factorio_mods_common::ModName>, Box<Future<Item = ::factorio_mods_web::ModFull, Error = ::factorio_mods_web::Error>>)>),
+	Download(Option<(::std::rc::Rc<::factorio_mods_common::ModName>, ::factorio_mods_web::ModRelease, Box<Future<Item = ::std::path::PathBuf, Error = ::Error>>)>),
 }
 
-struct DownloadFuture {
-	mod_name: ::std::rc::Rc<::factorio_mods_common::ModName>,
-	release_version: ::factorio_mods_common::ReleaseVersion,
-	chunk_stream: Box<Stream<Item = ::factorio_mods_web::reqwest::unstable::async::Chunk, Error = ::factorio_mods_web::Error>>,
-	download_file: ::std::io::BufWriter<::std::fs::File>,
-	download_filename: ::std::path::PathBuf,
-	download_displayable_filename: String,
-	filename: ::std::path::PathBuf,
-	displayable_filename: String,
+/// The result of a `CacheFuture` that completed.
+enum Event {
+	/// The releases of some mods were fetched. Mods that don't exist have no releases. The flag is true if they were fetched with `API::get_full`
+	Fetched(Vec<(::std::rc::Rc<::factorio_mods_common::ModName>, Vec<::factorio_mods_web::ModRelease>)>, bool),
+
+	/// A release was downloaded to the given file because its dependencies were not known.
+	Downloaded(::std::rc::Rc<::factorio_mods_common::ModName>, ::factorio_mods_web::ModRelease, ::std::path::PathBuf),
 }
 
+/// A package for the solver. Mods are described by the metadata of their releases on the mods portal, so they need not have been downloaded yet.
 #[derive(Clone, Debug)]
 enum Installable {
 	Base(::factorio_mods_common::ModName, ::factorio_mods_common::ReleaseVersion),
-	Mod(::factorio_mods_local::InstalledMod),
+	Mod {
+		name: ::factorio_mods_common::ModName,
+		release: ::factorio_mods_web::ModRelease,
+		dependencies: Vec<::factorio_mods_common::Dependency>,
+	},
+
+	/// A mod that is already installed, such as one linked into the mods directory from a working copy with `fac dev link`
+	Local(::factorio_mods_local::InstalledMod),
 }
 
 impl ::package::Package for Installable {
@@ -469,22 +540,115 @@ impl ::package::Package for Installable {
 
 	fn name(&self) -> &Self::Name {
 		match *self {
-			Installable::Base(ref name, _) => name,
-			Installable::Mod(ref cached_mod) => &cached_mod.info.name,
+			Installable::Base(ref name, _) |
+			Installable::Mod { ref name, .. } => name,
+			Installable::Local(ref installed_mod) => &installed_mod.info.name,
 		}
 	}
 
 	fn version(&self) -> &Self::Version {
 		match *self {
 			Installable::Base(_, ref version) => version,
-			Installable::Mod(ref cached_mod) => &cached_mod.info.version,
+			Installable::Mod { ref release, .. } => &release.version,
+			Installable::Local(ref installed_mod) => &installed_mod.info.version,
 		}
 	}
 
 	fn dependencies(&self) -> &[Self::Dependency] {
 		match *self {
 			Installable::Base(..) => &[],
-			Installable::Mod(ref cached_mod) => &cached_mod.info.dependencies,
+			Installable::Mod { ref dependencies, .. } => dependencies,
+			Installable::Local(ref installed_mod) => &installed_mod.info.dependencies,
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn version(version: &str) -> ::factorio_mods_common::ReleaseVersion {
+		::factorio_mods_common::ReleaseVersion(version.parse().unwrap())
+	}
+
+	fn dependency(name: &str) -> ::factorio_mods_common::Dependency {
+		::factorio_mods_common::Dependency {
+			name: ::factorio_mods_common::ModName(name.to_string()),
+			version: ::factorio_mods_common::ModVersionReq(::semver::VersionReq::any()),
+			required: true,
+		}
+	}
+
+	fn installed_mod(name: &str, version_: &str, dependencies: &[&str]) -> ::factorio_mods_local::InstalledMod {
+		::factorio_mods_local::InstalledMod {
+			path: format!("{}_{}.zip", name, version_).into(),
+			info: ::factorio_mods_local::ModInfo {
+				name: ::factorio_mods_common::ModName(name.to_string()),
+				author: vec![],
+				title: ::factorio_mods_common::ModTitle(name.to_string()),
+				description: None,
+				version: version(version_),
+				factorio_version: ::factorio_mods_common::ModVersionReq("0.16".parse().unwrap()),
+				homepage: None,
+				dependencies: dependencies.iter().map(|name| dependency(name)).collect(),
+			},
+			mod_type: ::factorio_mods_local::InstalledModType::Zipped,
+		}
+	}
+
+	fn portal_mod(name: &str, version_: &str, dependencies: &[&str]) -> Installable {
+		Installable::Mod {
+			name: ::factorio_mods_common::ModName(name.to_string()),
+			release: ::factorio_mods_web::ModRelease {
+				version: version(version_),
+				info_json: ::factorio_mods_web::ModReleaseInfo {
+					factorio_version: ::factorio_mods_common::ModVersionReq("0.16".parse().unwrap()),
+					dependencies: None,
+				},
+				download_url: ::factorio_mods_common::Url(format!("/download/{}", name)),
+				filename: ::factorio_mods_web::Filename(format!("{}_{}.zip", name, version_)),
+				released_at: ::factorio_mods_web::DateTime("2018-01-01T00:00:00.000000Z".to_string()),
+				sha1: ::factorio_mods_web::ModHash("0000000000000000000000000000000000000000".to_string()),
+			},
+			dependencies: dependencies.iter().map(|name| dependency(name)).collect(),
+		}
+	}
+
+	#[test]
+	fn install_missing_dependencies_does_not_upgrade_dependent() {
+		let game_version = version("0.16.51");
+
+		let dependent = installed_mod("dependent", "1.0.0", &["missing", "other"]);
+		let other = installed_mod("other", "1.0.0", &[]);
+		let unrelated = installed_mod("unrelated", "1.0.0", &[]);
+
+		let mut problem = Problem::new(&game_version, Default::default(), vec![dependent], vec![other, unrelated]);
+
+		// Only the missing dependency is fetched from the mods portal.
+		let queued: Vec<_> = problem.queued.iter().map(|mod_name| mod_name.0.clone()).collect();
+		assert_eq!(queued, vec!["missing"]);
+
+		// Even if the mods portal has a newer release of the dependent, the installed version is kept.
+		problem.packages.push(portal_mod("dependent", "2.0.0", &["missing", "other"]));
+		problem.packages.push(portal_mod("missing", "1.0.0", &[]));
+		problem.packages.push(portal_mod("missing", "1.1.0", &[]));
+
+		let solution = ::package::compute_solution(problem.packages, &problem.reqs).unwrap().unwrap();
+
+		let mut solution: Vec<_> =
+			solution.into_iter()
+			.map(|(name, installable)| {
+				let is_local = match installable { Installable::Local(_) => true, _ => false };
+				(name.0, ::package::Package::version(&installable).to_string(), is_local)
+			})
+			.collect();
+		solution.sort();
+
+		assert_eq!(solution, vec![
+			("base".to_string(), "0.16.51".to_string(), false),
+			("dependent".to_string(), "1.0.0".to_string(), true),
+			("missing".to_string(), "1.1.0".to_string(), false),
+			("other".to_string(), "1.0.0".to_string(), true),
+		]);
+	}
+}
@@ -13,15 +13,15 @@ impl ::util::SubCommand for SubCommand {
 		_: &'a ::clap::ArgMatches<'a>,
 		local_api: ::Result<&'a ::factorio_mods_local::API>,
 		web_api: ::Result<&'a ::factorio_mods_web::API>,
-		prompt_override: Option<bool>,
+		global_options: ::util::GlobalOptions<'a>,
 	) -> Box<Future<Item = (), Error = ::Error> + 'a> {
 		Box::new(::async_block! {
 			let local_api = local_api?;
 			let web_api = web_api?;
 
-			let config = ::config::Config::load(local_api)?;
+			let config = ::config::Config::load(local_api, global_options.wait_for_lock)?;
 
-			::await!(::solve::compute_and_apply_diff(local_api, web_api, config, prompt_override))?;
+			::await!(::solve::compute_and_apply_diff(local_api, web_api, config, global_options))?;
 
 			Ok(())
 		})
@@ -7,10 +7,48 @@ pub trait SubCommand {
 		matches: &'a ::clap::ArgMatches<'a>,
 		local_api: ::Result<&'a ::factorio_mods_local::API>,
 		web_api: ::Result<&'a ::factorio_mods_web::API>,
-		prompt_override: Option<bool>,
+		global_options: GlobalOptions<'a>,
 	) -> Box<Future<Item = (), Error = ::Error> + 'a>;
 }
 
+/// Options that apply to all subcommands.
+#[derive(Clone, Copy, Debug)]
+pub struct GlobalOptions<'a> {
+	/// The answer to give to all prompts, if any.
+	pub prompt_override: Option<bool>,
+
+	/// Print the changes that would be made without making them.
+	pub dry_run: bool,
+
+	/// The file to save the computed plan to, in dry-run mode.
+	pub save_plan: Option<&'a str>,
+
+	/// The format of the output of subcommands that list things.
+	pub output_format: OutputFormat,
+
+	/// The maximum number of mod releases to download at the same time.
+	pub max_concurrent_downloads: usize,
+
+	/// How to install mods from the cache into the mods directory.
+	pub install_strategy: ::link::InstallStrategy,
+
+	/// Whether mods that are installed for the first time are enabled in `mod-list.json`
+	pub enable_new_mods: bool,
+
+	/// Wait for other instances of fac to finish instead of failing.
+	pub wait_for_lock: bool,
+}
+
+/// The format of the output of subcommands that list things.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OutputFormat {
+	/// Human-readable text.
+	Text,
+
+	/// JSON records, as defined in the `output` module.
+	Json,
+}
+
 pub fn wrapping_println(s: &str, indent: &str) {
 	#[cfg_attr(feature = "cargo-clippy", allow(single_match_else))] // Bad clippy lint - false positive
 	match ::term_size::dimensions() {
@@ -31,7 +69,9 @@ pub fn wrapping_println(s: &str, indent: &str) {
 		},
 
 		None =>
-			println!("{}{}", indent, s),
+			for line in s.split('\n') {
+				println!("{}{}", indent, line);
+			},
 	}
 }
 
@@ -125,3 +165,21 @@ pub fn prompt_continue(prompt_override: Option<bool>) -> ::Result<bool> {
 	}
 
 }
+
+/// Formats the given number of bytes with a binary unit prefix and one decimal place.
+pub fn format_bytes(bytes: u64) -> String {
+	const UNITS: &[(u64, &str)] = &[(1 << 30, "GiB"), (1 << 20, "MiB"), (1 << 10, "KiB")];
+
+	for &(size, unit) in UNITS {
+		if bytes >= size {
+			return format!("{}.{} {}", bytes / size, bytes % size * 10 / size, unit);
+		}
+	}
+
+	format!("{} B", bytes)
+}
+
+/// Returns whether the given mod name is accepted by the mods portal, which only allows up to 100 letters, digits, dashes and underscores.
+pub fn is_valid_mod_name(name: &str) -> bool {
+	!name.is_empty() && name.len() <= 100 && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}